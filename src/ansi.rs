@@ -0,0 +1,149 @@
+use ratatui::{
+    style::{Color, Modifier, Style},
+    text::{Line, Span},
+};
+
+/// Converts a line containing ANSI SGR escape sequences into styled spans.
+///
+/// Styles carry over from previous lines, so the returned style has to be passed on to the next
+/// line. Other escape sequences are dropped.
+pub fn parse_line(line: &str, mut style: Style) -> (Line<'static>, Style) {
+    let mut spans = Vec::new();
+    let mut text = String::new();
+    let mut rest = line;
+
+    while let Some(start) = rest.find('\x1b') {
+        text.push_str(&rest[..start]);
+        rest = &rest[start + 1..];
+        let Some(params) = rest.strip_prefix('[') else {
+            continue;
+        };
+        let Some(end) = params.find(|c: char| c.is_ascii_alphabetic()) else {
+            rest = "";
+            break;
+        };
+        if &params[end..=end] == "m" {
+            if !text.is_empty() {
+                spans.push(Span::styled(std::mem::take(&mut text), style));
+            }
+            style = apply_sgr(style, &params[..end]);
+        }
+        rest = &params[end + 1..];
+    }
+    text.push_str(rest);
+    if !text.is_empty() {
+        spans.push(Span::styled(text, style));
+    }
+
+    (Line::from(spans), style)
+}
+
+/// Removes all ANSI escape sequences, e.g. for copying colored output to the clipboard.
+pub fn strip(text: &str) -> String {
+    text.lines()
+        .scan(Style::default(), |style, line| {
+            let (line, next) = parse_line(line, *style);
+            *style = next;
+            Some(line.to_string())
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+fn apply_sgr(mut style: Style, params: &str) -> Style {
+    let mut codes = params
+        .split(';')
+        .map(|code| code.parse::<u8>().unwrap_or_default());
+    while let Some(code) = codes.next() {
+        style = match code {
+            0 => Style::default(),
+            1 => style.add_modifier(Modifier::BOLD),
+            2 => style.add_modifier(Modifier::DIM),
+            3 => style.add_modifier(Modifier::ITALIC),
+            4 => style.add_modifier(Modifier::UNDERLINED),
+            7 => style.add_modifier(Modifier::REVERSED),
+            22 => style.remove_modifier(Modifier::BOLD | Modifier::DIM),
+            23 => style.remove_modifier(Modifier::ITALIC),
+            24 => style.remove_modifier(Modifier::UNDERLINED),
+            27 => style.remove_modifier(Modifier::REVERSED),
+            30..=37 => style.fg(Color::Indexed(code - 30)),
+            38 => match extended_color(&mut codes) {
+                Some(color) => style.fg(color),
+                None => style,
+            },
+            39 => style.fg(Color::Reset),
+            40..=47 => style.bg(Color::Indexed(code - 40)),
+            48 => match extended_color(&mut codes) {
+                Some(color) => style.bg(color),
+                None => style,
+            },
+            49 => style.bg(Color::Reset),
+            90..=97 => style.fg(Color::Indexed(code - 90 + 8)),
+            100..=107 => style.bg(Color::Indexed(code - 100 + 8)),
+            _ => style,
+        };
+    }
+    style
+}
+
+fn extended_color(codes: &mut impl Iterator<Item = u8>) -> Option<Color> {
+    match codes.next()? {
+        5 => codes.next().map(Color::Indexed),
+        2 => Some(Color::Rgb(codes.next()?, codes.next()?, codes.next()?)),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_basic_colors_and_reset() {
+        let (line, style) = parse_line("\x1b[1;31mremoved\x1b[0m plain", Style::default());
+        assert_eq!(line.spans.len(), 2);
+        assert_eq!(line.spans[0].content, "removed");
+        assert_eq!(
+            line.spans[0].style,
+            Style::default()
+                .fg(Color::Indexed(1))
+                .add_modifier(Modifier::BOLD)
+        );
+        assert_eq!(line.spans[1].content, " plain");
+        assert_eq!(line.spans[1].style, Style::default());
+        assert_eq!(style, Style::default());
+    }
+
+    #[test]
+    fn parse_extended_and_bright_colors() {
+        let (line, _) = parse_line(
+            "\x1b[38;5;208ma\x1b[48;2;1;2;3mb\x1b[92mc",
+            Style::default(),
+        );
+        let styles: Vec<Style> = line.spans.iter().map(|span| span.style).collect();
+        let orange = Style::default().fg(Color::Indexed(208));
+        assert_eq!(
+            styles,
+            [
+                orange,
+                orange.bg(Color::Rgb(1, 2, 3)),
+                orange.bg(Color::Rgb(1, 2, 3)).fg(Color::Indexed(10)),
+            ]
+        );
+    }
+
+    #[test]
+    fn style_carries_over_to_the_next_line() {
+        let (_, style) = parse_line("\x1b[32mstart", Style::default());
+        let (line, _) = parse_line("continued\x1b[39m", style);
+        assert_eq!(line.spans[0].style, Style::default().fg(Color::Indexed(2)));
+    }
+
+    #[test]
+    fn strip_drops_all_escape_sequences() {
+        assert_eq!(
+            strip("\x1b[1m\x1b[38;5;1m-old\x1b[0m\n\x1b[2K+new\x1b[m"),
+            "-old\n+new"
+        );
+    }
+}
@@ -0,0 +1,106 @@
+use std::{fmt::Display, sync::Arc};
+
+use diffsoup::{diff::DiffOptions, pr::PrFetcher};
+use jj_lib::{repo::ReadonlyRepo, workspace::Workspace};
+
+use crate::{
+    DiffArgs,
+    show::{BranchComparison, PatchsetArgs},
+};
+
+#[derive(clap::Args, Debug)]
+pub struct CheckArgs {
+    #[command(flatten)]
+    pub diff: DiffArgs,
+
+    #[command(flatten)]
+    patchsets: PatchsetArgs,
+}
+
+/// The most significant change between two patchsets.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Verdict {
+    /// The very same commits
+    Unchanged,
+    /// Rewritten without any visible change, e.g. rebased onto a newer trunk
+    Rebased,
+    /// Only descriptions changed
+    Reworded,
+    /// Metadata like the author or the parents changed, which needs another look
+    MetadataChanged,
+    /// The contents of at least one commit changed, or commits were added or removed
+    CodeChanged,
+}
+
+impl Verdict {
+    pub fn exit_code(self) -> u8 {
+        match self {
+            Self::Unchanged | Self::Rebased | Self::Reworded => 0,
+            Self::MetadataChanged | Self::CodeChanged => 1,
+        }
+    }
+}
+
+impl Display for Verdict {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Unchanged => write!(f, "unchanged"),
+            Self::Rebased => write!(f, "rebased"),
+            Self::Reworded => write!(f, "reworded"),
+            Self::MetadataChanged => write!(f, "metadata-changed"),
+            Self::CodeChanged => write!(f, "code-changed"),
+        }
+    }
+}
+
+/// Compares two patchsets and prints the verdict on the first line, followed by one tab
+/// separated line per changed commit: the reason (`code`, `message` or `metadata`), the match
+/// status, the sha and the subject.
+pub fn run(
+    workspace: Workspace,
+    repo: Arc<ReadonlyRepo>,
+    pr_fetcher: Box<dyn PrFetcher>,
+    options: DiffOptions,
+    args: &CheckArgs,
+) -> Result<Verdict, Box<dyn std::error::Error>> {
+    // A filter, ignored whitespace or hidden merges could hide code changes and let the check pass
+    let options = options.exact();
+    let BranchComparison { commits, .. } =
+        args.patchsets
+            .compare(&workspace, repo, pr_fetcher.as_ref(), &options)?;
+
+    let mut verdict = Verdict::Unchanged;
+    let mut reasons = Vec::new();
+    for commit in &commits {
+        let reason = match (&commit.from, &commit.to) {
+            _ if commit.has_code_changes() => Some(("code", Verdict::CodeChanged)),
+            (Some(from), Some(to)) if from.message != to.message => {
+                Some(("message", Verdict::Reworded))
+            }
+            _ if commit.has_changes() => Some(("metadata", Verdict::MetadataChanged)),
+            (Some(from), Some(to)) if from.sha != to.sha => {
+                verdict = verdict.max(Verdict::Rebased);
+                None
+            }
+            _ => None,
+        };
+        let Some((reason, commit_verdict)) = reason else {
+            continue;
+        };
+        verdict = verdict.max(commit_verdict);
+        let meta = commit.to.as_ref().or(commit.from.as_ref());
+        reasons.push(format!(
+            "{reason}\t{}\t{}\t{}",
+            commit.status(),
+            meta.map(|meta| meta.sha.as_str()).unwrap_or_default(),
+            meta.and_then(|meta| meta.message.lines().next())
+                .unwrap_or_default()
+        ));
+    }
+
+    println!("{verdict}");
+    for reason in reasons {
+        println!("{reason}");
+    }
+    Ok(verdict)
+}
@@ -0,0 +1,368 @@
+use crate::{
+    error::{CustomError, Result},
+    external::read_file,
+};
+use error_stack::ResultExt;
+use futures::StreamExt;
+use jj_lib::{
+    backend::{FileId, TreeValue},
+    git_backend::GitBackend,
+    matchers::Matcher,
+    merge::MergedTreeValue,
+    merged_tree::MergedTree,
+    object_id::ObjectId,
+    repo::Repo,
+    repo_path::RepoPathBuf,
+};
+
+/// Files above either threshold, and all binary files, are only summarized instead of diffed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CollapseThresholds {
+    pub max_bytes: usize,
+    pub max_lines: usize,
+}
+
+impl Default for CollapseThresholds {
+    fn default() -> Self {
+        Self {
+            max_bytes: 512 * 1024,
+            max_lines: 5000,
+        }
+    }
+}
+
+/// A file whose diff was replaced by a summary.
+#[derive(Debug, Clone)]
+pub struct CollapsedFile {
+    pub path: RepoPathBuf,
+    pub binary: bool,
+    pub before: Option<FileSummary>,
+    pub after: Option<FileSummary>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileSummary {
+    pub size: usize,
+    /// `None` if the file is above the size threshold and wasn't read
+    pub lines: Option<usize>,
+    /// Hex id of the file content
+    pub hash: String,
+    /// Width and height if the content is a common image format
+    pub image: Option<(&'static str, u32, u32)>,
+}
+
+/// Finds the changed files between two trees that are binary or exceed the thresholds.
+///
+/// Conflicted files are never collapsed, their sides are rendered separately anyway.
+pub fn find_collapsed_files(
+    from_tree: &MergedTree,
+    to_tree: &MergedTree,
+    matcher: &dyn Matcher,
+    thresholds: &CollapseThresholds,
+    repo: &impl Repo,
+) -> Result<Vec<CollapsedFile>> {
+    let entries =
+        futures::executor::block_on(from_tree.diff_stream(to_tree, matcher).collect::<Vec<_>>());
+
+    let mut collapsed = Vec::new();
+    for entry in entries {
+        let values = entry.values.change_context(CustomError::RepoError)?;
+        let (Some(before), Some(after)) = (
+            read_side(&entry.path, values.before, thresholds, repo)?,
+            read_side(&entry.path, values.after, thresholds, repo)?,
+        ) else {
+            continue;
+        };
+        let binary = [&before, &after]
+            .iter()
+            .any(|side| side.as_ref().is_some_and(|(_, binary)| *binary));
+        let too_large = [&before, &after].iter().any(|side| {
+            side.as_ref().is_some_and(|(summary, _)| {
+                summary.size > thresholds.max_bytes
+                    || summary
+                        .lines
+                        .is_some_and(|lines| lines > thresholds.max_lines)
+            })
+        });
+        if binary || too_large {
+            collapsed.push(CollapsedFile {
+                path: entry.path,
+                binary,
+                before: before.map(|(summary, _)| summary),
+                after: after.map(|(summary, _)| summary),
+            });
+        }
+    }
+    Ok(collapsed)
+}
+
+/// Reads one side of a changed file. The outer `None` means the side can't be summarized, e.g.
+/// because it's conflicted, the inner one that the file is absent.
+///
+/// Files above the size threshold are summarized from the object header without reading them.
+fn read_side(
+    path: &RepoPathBuf,
+    value: MergedTreeValue,
+    thresholds: &CollapseThresholds,
+    repo: &impl Repo,
+) -> Result<Option<Option<(FileSummary, bool)>>> {
+    let id = match value.into_resolved() {
+        Ok(None) => return Ok(Some(None)),
+        Ok(Some(TreeValue::File { id, .. })) => id,
+        Ok(Some(_)) | Err(_) => return Ok(None),
+    };
+    if let Some(size) = blob_size(&id, repo)?
+        && size > thresholds.max_bytes
+    {
+        let summary = FileSummary {
+            size,
+            lines: None,
+            hash: id.hex(),
+            image: None,
+        };
+        return Ok(Some(Some((summary, false))));
+    }
+    let contents = read_file(path, &id, repo)?;
+    // Same heuristic as git, a NUL byte near the start means binary
+    let binary = contents.iter().take(8000).any(|byte| *byte == 0);
+    let summary = FileSummary {
+        size: contents.len(),
+        lines: Some(contents.iter().filter(|byte| **byte == b'\n').count()),
+        hash: id.hex(),
+        image: image_dimensions(&contents),
+    };
+    Ok(Some(Some((summary, binary))))
+}
+
+/// Size of a blob from its object header, if the repo is backed by git.
+fn blob_size(id: &FileId, repo: &impl Repo) -> Result<Option<usize>> {
+    let Some(git_backend) = repo.store().backend_impl::<GitBackend>() else {
+        return Ok(None);
+    };
+    let object_id =
+        gix::ObjectId::try_from(id.as_bytes()).change_context(CustomError::RepoError)?;
+    let header = git_backend
+        .git_repo()
+        .find_header(object_id)
+        .change_context(CustomError::RepoError)?;
+    Ok(usize::try_from(header.size()).ok())
+}
+
+/// Renders one line per collapsed file, describing both sides.
+pub fn render_collapsed_files(files: &[CollapsedFile]) -> String {
+    let mut output = String::new();
+    for file in files {
+        let path = file.path.as_internal_file_string();
+        let lines = |summary: &Option<FileSummary>| summary.as_ref().and_then(|s| s.lines);
+        let kind = match (file.binary, lines(&file.before), lines(&file.after)) {
+            (true, _, _) => "binary".to_string(),
+            (false, Some(before), Some(after)) => format!("{before} → {after} lines"),
+            (false, _, _) => "large file".to_string(),
+        };
+        output.push_str(&format!(
+            "{path}: {kind}, {} → {}\n",
+            describe(file.before.as_ref()),
+            describe(file.after.as_ref())
+        ));
+    }
+    output
+}
+
+fn describe(summary: Option<&FileSummary>) -> String {
+    let Some(summary) = summary else {
+        return "(absent)".to_string();
+    };
+    let hash = summary.hash.get(..12).unwrap_or(&summary.hash);
+    let mut description = format!("{}, {hash}", format_size(summary.size));
+    if let Some((format, width, height)) = summary.image {
+        description.push_str(&format!(", {format} {width}×{height}"));
+    }
+    description
+}
+
+fn format_size(size: usize) -> String {
+    const UNITS: [&str; 4] = ["B", "KiB", "MiB", "GiB"];
+    let mut value = size as f64;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{size} B")
+    } else {
+        format!("{value:.1} {}", UNITS[unit])
+    }
+}
+
+/// Reads the dimensions from the header of PNG, GIF, JPEG, BMP and WebP images.
+fn image_dimensions(contents: &[u8]) -> Option<(&'static str, u32, u32)> {
+    let u16_le = |at: usize| {
+        Some(u16::from_le_bytes(
+            contents.get(at..at + 2)?.try_into().ok()?,
+        ))
+    };
+    let u16_be = |at: usize| {
+        Some(u16::from_be_bytes(
+            contents.get(at..at + 2)?.try_into().ok()?,
+        ))
+    };
+    let u32_le = |at: usize| {
+        Some(u32::from_le_bytes(
+            contents.get(at..at + 4)?.try_into().ok()?,
+        ))
+    };
+    let u32_be = |at: usize| {
+        Some(u32::from_be_bytes(
+            contents.get(at..at + 4)?.try_into().ok()?,
+        ))
+    };
+
+    if contents.starts_with(b"\x89PNG\r\n\x1a\n") {
+        return Some(("PNG", u32_be(16)?, u32_be(20)?));
+    }
+    if contents.starts_with(b"GIF87a") || contents.starts_with(b"GIF89a") {
+        return Some(("GIF", u16_le(6)?.into(), u16_le(8)?.into()));
+    }
+    if contents.starts_with(b"BM") {
+        let height = i32::from_le_bytes(contents.get(22..26)?.try_into().ok()?);
+        return Some(("BMP", u32_le(18)?, height.unsigned_abs()));
+    }
+    if contents.starts_with(b"RIFF") && contents.get(8..12) == Some(b"WEBP".as_slice()) {
+        return match contents.get(12..16)? {
+            b"VP8X" => {
+                let u24_le = |at: usize| {
+                    let bytes = contents.get(at..at + 3)?;
+                    Some(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], 0]))
+                };
+                Some(("WebP", u24_le(24)? + 1, u24_le(27)? + 1))
+            }
+            b"VP8L" => {
+                let bits = u32_le(21)?;
+                Some(("WebP", (bits & 0x3fff) + 1, ((bits >> 14) & 0x3fff) + 1))
+            }
+            b"VP8 " => Some((
+                "WebP",
+                (u16_le(26)? & 0x3fff).into(),
+                (u16_le(28)? & 0x3fff).into(),
+            )),
+            _ => None,
+        };
+    }
+    if contents.starts_with(b"\xff\xd8") {
+        // Walk the segments until a start of frame marker, which holds the dimensions
+        let mut at = 2;
+        while at + 4 <= contents.len() {
+            if contents[at] != 0xff {
+                return None;
+            }
+            let marker = contents[at + 1];
+            let length = usize::from(u16_be(at + 2)?);
+            let is_start_of_frame =
+                matches!(marker, 0xc0..=0xcf) && !matches!(marker, 0xc4 | 0xc8 | 0xcc);
+            if is_start_of_frame {
+                return Some(("JPEG", u16_be(at + 7)?.into(), u16_be(at + 5)?.into()));
+            }
+            at += 2 + length;
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// `len` zero bytes with `bytes` written at each offset.
+    fn header(len: usize, parts: &[(usize, &[u8])]) -> Vec<u8> {
+        let mut contents = vec![0; len];
+        for (at, bytes) in parts {
+            contents[*at..*at + bytes.len()].copy_from_slice(bytes);
+        }
+        contents
+    }
+
+    #[test]
+    fn png_dimensions() {
+        let png = header(
+            24,
+            &[
+                (0, b"\x89PNG\r\n\x1a\n"),
+                (16, &640u32.to_be_bytes()),
+                (20, &480u32.to_be_bytes()),
+            ],
+        );
+        assert_eq!(image_dimensions(&png), Some(("PNG", 640, 480)));
+    }
+
+    #[test]
+    fn gif_dimensions() {
+        let gif = header(
+            10,
+            &[
+                (0, b"GIF89a"),
+                (6, &32u16.to_le_bytes()),
+                (8, &16u16.to_le_bytes()),
+            ],
+        );
+        assert_eq!(image_dimensions(&gif), Some(("GIF", 32, 16)));
+    }
+
+    #[test]
+    fn bmp_dimensions_of_top_down_images() {
+        let bmp = header(
+            26,
+            &[
+                (0, b"BM"),
+                (18, &100i32.to_le_bytes()),
+                (22, &(-50i32).to_le_bytes()),
+            ],
+        );
+        assert_eq!(image_dimensions(&bmp), Some(("BMP", 100, 50)));
+    }
+
+    #[test]
+    fn webp_dimensions() {
+        let riff = |chunk: &[u8]| [b"RIFF\0\0\0\0WEBP".as_slice(), chunk].concat();
+        let extended = header(
+            30,
+            &[(0, &riff(b"VP8X")), (24, &[199, 0, 0]), (27, &[99, 0, 0])],
+        );
+        assert_eq!(image_dimensions(&extended), Some(("WebP", 200, 100)));
+
+        // 14 bits each for width and height minus one
+        let bits: u32 = 299 | (149 << 14);
+        let lossless = header(25, &[(0, &riff(b"VP8L")), (21, &bits.to_le_bytes())]);
+        assert_eq!(image_dimensions(&lossless), Some(("WebP", 300, 150)));
+
+        let lossy = header(
+            30,
+            &[
+                (0, &riff(b"VP8 ")),
+                (26, &64u16.to_le_bytes()),
+                (28, &48u16.to_le_bytes()),
+            ],
+        );
+        assert_eq!(image_dimensions(&lossy), Some(("WebP", 64, 48)));
+    }
+
+    #[test]
+    fn jpeg_dimensions_after_other_segments() {
+        let jpeg = [
+            b"\xff\xd8".as_slice(),
+            // APP0 and a huffman table, which must not be mistaken for a start of frame
+            b"\xff\xe0\x00\x04\x00\x00",
+            b"\xff\xc4\x00\x04\x00\x00",
+            b"\xff\xc0\x00\x0b\x08\x01\xe0\x02\x80\x03\x00",
+        ]
+        .concat();
+        assert_eq!(image_dimensions(&jpeg), Some(("JPEG", 640, 480)));
+    }
+
+    #[test]
+    fn unknown_or_truncated_images() {
+        assert_eq!(image_dimensions(b"plain text"), None);
+        assert_eq!(image_dimensions(b"\x89PNG\r\n\x1a\n\0\0"), None);
+        assert_eq!(image_dimensions(b"\xff\xd8\xff\xe0\x00\x10"), None);
+    }
+}
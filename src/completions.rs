@@ -0,0 +1,152 @@
+use std::{ffi::OsStr, io, path::Path};
+
+use clap::CommandFactory;
+use clap_complete::{CompletionCandidate, Shell, env::Shells};
+use diffsoup::repo::{self, RepoHandle};
+use jj_lib::{git::REMOTE_NAME_FOR_LOCAL_GIT_REPO, git_backend::GitBackend, repo::Repo};
+
+use crate::Cli;
+
+#[derive(clap::Args, Debug)]
+pub struct CompletionsArgs {
+    shell: Shell,
+
+    /// Register the completions for `jj`, when diffsoup is installed as `jj` to provide
+    /// `jj diffsoup`
+    #[arg(long)]
+    jj: bool,
+}
+
+/// Prints the script that makes the shell ask diffsoup for completions, which are then
+/// generated by `CompleteEnv` in `main`, or by jj's own completion when running as `jj`.
+pub fn run(args: &CompletionsArgs) -> io::Result<()> {
+    let shell = args.shell.to_string();
+    let shells = Shells::builtins();
+    let Some(completer) = shells.completer(&shell) else {
+        return Err(io::Error::other(format!("unsupported shell: {shell}")));
+    };
+    let name = if args.jj { "jj" } else { "diffsoup" };
+    completer.write_registration("COMPLETE", name, name, name, &mut io::stdout())
+}
+
+/// Writes the man page for all options and subcommands.
+pub fn man() -> io::Result<()> {
+    clap_mangen::Man::new(Cli::command()).render(&mut io::stdout())
+}
+
+/// Opens the repo in the current directory the same way as the commands do, so that
+/// non-colocated jj workspaces work too.
+fn open_repo() -> Option<RepoHandle> {
+    repo::open(Path::new(".")).ok()
+}
+
+/// Completes the pull request URL prefix of every remote of the repo in the current directory.
+pub fn complete_pr_url(current: &OsStr) -> Vec<CompletionCandidate> {
+    let Some(current) = current.to_str() else {
+        return Vec::new();
+    };
+    let Some(handle) = open_repo() else {
+        return Vec::new();
+    };
+    let Some(git_backend) = handle.repo.store().backend_impl::<GitBackend>() else {
+        return Vec::new();
+    };
+    let repo = git_backend.git_repo();
+    let mut urls: Vec<String> = repo
+        .remote_names()
+        .iter()
+        .filter_map(|name| repo.find_remote(name.as_ref()).ok())
+        .filter_map(|remote| {
+            remote
+                .url(gix::remote::Direction::Fetch)
+                .and_then(pr_url_prefix)
+        })
+        .filter(|url| url.starts_with(current))
+        .collect();
+    urls.sort();
+    urls.dedup();
+    urls.into_iter().map(CompletionCandidate::new).collect()
+}
+
+/// Converts a clone URL into the URL of its pull requests, without the number.
+fn pr_url_prefix(url: &gix::Url) -> Option<String> {
+    let host = url.host()?;
+    let path = url.path.to_string();
+    let path = path.trim_matches('/').trim_end_matches(".git");
+    // Bitbucket Server clones over `/scm/` with http and from the top level with ssh
+    let bitbucket_path = path
+        .strip_prefix("scm/")
+        .or(host.contains("bitbucket").then_some(path));
+    if let Some(path) = bitbucket_path {
+        let (project, repo) = path.split_once('/')?;
+        return Some(format!(
+            "https://{host}/projects/{project}/repos/{repo}/pull-requests/"
+        ));
+    }
+    let (owner, repo) = path.split_once('/')?;
+    Some(format!("https://{host}/{owner}/{repo}/pull/"))
+}
+
+/// Completes the local and remote bookmarks of the repo in the current directory.
+pub fn complete_bookmark(current: &OsStr) -> Vec<CompletionCandidate> {
+    let Some(current) = current.to_str() else {
+        return Vec::new();
+    };
+    let Some(handle) = open_repo() else {
+        return Vec::new();
+    };
+    let view = handle.repo.view();
+    let local = view
+        .local_bookmarks()
+        .map(|(name, _)| name.as_str().to_string());
+    let remote = view
+        .all_remote_bookmarks()
+        .filter(|(symbol, remote_ref)| {
+            symbol.remote != REMOTE_NAME_FOR_LOCAL_GIT_REPO && remote_ref.target.is_present()
+        })
+        .map(|(symbol, _)| symbol.to_string());
+    let mut names: Vec<String> = local
+        .chain(remote)
+        .filter(|name| name.starts_with(current))
+        .collect();
+    names.sort();
+    names.dedup();
+    names.into_iter().map(CompletionCandidate::new).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn prefix(url: &str) -> Option<String> {
+        pr_url_prefix(&gix::Url::try_from(url).unwrap())
+    }
+
+    #[test]
+    fn github_urls() {
+        let expected = Some("https://github.com/owner/repo/pull/".to_string());
+        assert_eq!(prefix("https://github.com/owner/repo.git"), expected);
+        assert_eq!(prefix("git@github.com:owner/repo.git"), expected);
+        assert_eq!(prefix("ssh://git@github.com/owner/repo"), expected);
+    }
+
+    #[test]
+    fn bitbucket_server_urls() {
+        let expected = Some(
+            "https://bitbucket.example.com/projects/PRJ/repos/repo/pull-requests/".to_string(),
+        );
+        assert_eq!(
+            prefix("https://bitbucket.example.com/scm/PRJ/repo.git"),
+            expected
+        );
+        assert_eq!(
+            prefix("ssh://git@bitbucket.example.com:7999/PRJ/repo.git"),
+            expected
+        );
+    }
+
+    #[test]
+    fn urls_without_owner() {
+        assert_eq!(prefix("https://example.com/repo.git"), None);
+    }
+}
@@ -0,0 +1,133 @@
+use crate::{
+    diff::RenderFormat,
+    error::{CustomError, Result},
+};
+use error_stack::{Report, ResultExt};
+use jj_lib::{
+    config::{ConfigGetResultExt, ConfigLayer, ConfigSource},
+    settings::UserSettings,
+};
+use std::process::Command;
+
+/// Defaults of the `[diffsoup]` table, added to the bottom of the jj config stack so that the user,
+/// repo and workspace configs can override them.
+pub fn default_layer() -> ConfigLayer {
+    ConfigLayer::parse(ConfigSource::Default, include_str!("config/defaults.toml"))
+        .expect("builtin config should be valid")
+}
+
+/// The `[diffsoup]` settings for fetching and diffing, the TUI reads its own.
+#[derive(Debug, Clone)]
+pub struct Config {
+    /// Used unless `--diff-format` is given
+    pub diff_format: RenderFormat,
+    /// Fileset used unless `--filter` is given
+    pub filter: Option<String>,
+    pub github: ForgeConfig,
+    pub bitbucket: ForgeConfig,
+}
+
+/// How to reach one kind of forge.
+#[derive(Debug, Clone)]
+pub struct ForgeConfig {
+    /// Hosts of the pull request URLs, which also match their subdomains
+    pub hosts: Vec<String>,
+    /// Run to get a token if the environment variable is unset
+    pub token_command: Option<Vec<String>>,
+    /// Number of items fetched per request, if the API is paginated
+    pub page_size: Option<usize>,
+}
+
+impl Config {
+    pub fn from_settings(settings: &UserSettings) -> Result<Self> {
+        let diff_format = settings
+            .get_string("diffsoup.diff-format")
+            .change_context(CustomError::ConfigError)?;
+        Ok(Self {
+            diff_format: diff_format
+                .parse()
+                .map_err(|e: String| Report::new(CustomError::ConfigError).attach(e))?,
+            filter: settings
+                .get_string("diffsoup.filter")
+                .optional()
+                .change_context(CustomError::ConfigError)?,
+            github: ForgeConfig::from_settings(settings, "github")?,
+            bitbucket: ForgeConfig::from_settings(settings, "bitbucket")?,
+        })
+    }
+}
+
+impl ForgeConfig {
+    fn from_settings(settings: &UserSettings, forge: &str) -> Result<Self> {
+        Ok(Self {
+            hosts: settings
+                .get::<Vec<String>>(["diffsoup", forge, "hosts"])
+                .change_context(CustomError::ConfigError)?,
+            token_command: settings
+                .get::<Vec<String>>(["diffsoup", forge, "token-command"])
+                .optional()
+                .change_context(CustomError::ConfigError)?,
+            page_size: settings
+                .get::<usize>(["diffsoup", forge, "page-size"])
+                .optional()
+                .change_context(CustomError::ConfigError)?,
+        })
+    }
+
+    /// Whether `host` is one of the hosts or a subdomain of one.
+    pub fn matches(&self, host: &str) -> bool {
+        let host = host.to_ascii_lowercase();
+        self.hosts.iter().any(|pattern| {
+            let pattern = pattern.to_ascii_lowercase();
+            host.strip_suffix(pattern.as_str())
+                .is_some_and(|prefix| prefix.is_empty() || prefix.ends_with('.'))
+        })
+    }
+
+    /// Reads the token from `env_var`, or runs the token command if it is unset.
+    pub fn token(&self, env_var: &str) -> Result<Option<String>> {
+        if let Ok(token) = std::env::var(env_var) {
+            return Ok(Some(token));
+        }
+        let Some((program, args)) = self.token_command.as_ref().and_then(|c| c.split_first())
+        else {
+            return Ok(None);
+        };
+        let output = Command::new(program)
+            .args(args)
+            .output()
+            .change_context(CustomError::ConfigError)
+            .attach(format!("could not run token command `{program}`"))?;
+        if !output.status.success() {
+            return Err(Report::new(CustomError::ConfigError).attach(format!(
+                "token command `{program}` failed: {}",
+                String::from_utf8_lossy(&output.stderr).trim()
+            )));
+        }
+        let token = String::from_utf8_lossy(&output.stdout).trim().to_string();
+        Ok((!token.is_empty()).then_some(token))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn matches_hosts_and_subdomains() {
+        let config = ForgeConfig {
+            hosts: vec![
+                "github.com".to_string(),
+                "Bitbucket.Example.com".to_string(),
+            ],
+            token_command: None,
+            page_size: None,
+        };
+        assert!(config.matches("github.com"));
+        assert!(config.matches("api.github.com"));
+        assert!(config.matches("bitbucket.example.com"));
+        assert!(!config.matches("notgithub.com"));
+        assert!(!config.matches("github.com.evil.org"));
+        assert!(!config.matches("example.com"));
+    }
+}
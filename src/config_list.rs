@@ -0,0 +1,47 @@
+use std::path::PathBuf;
+
+use diffsoup::repo::RepoHandle;
+use jj_cli::config::resolved_config_values;
+use jj_lib::config::ConfigNamePathBuf;
+
+use crate::open_repo;
+
+#[derive(clap::Args, Debug)]
+pub struct ConfigArgs {
+    #[command(subcommand)]
+    command: ConfigCommand,
+}
+
+#[derive(clap::Subcommand, Debug)]
+enum ConfigCommand {
+    /// Print the effective `[diffsoup]` settings and the config layer each one comes from
+    List {
+        #[arg(short, long, default_value = ".")]
+        repo: PathBuf,
+    },
+}
+
+pub fn run(
+    args: &ConfigArgs,
+    loaded: Option<RepoHandle>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let ConfigCommand::List { repo } = &args.command;
+    // The repo and workspace configs can override the user config
+    let handle = open_repo(repo, loaded)?;
+    let prefix = ConfigNamePathBuf::from_iter(["diffsoup"]);
+    for value in resolved_config_values(handle.workspace.settings().config(), &prefix) {
+        if value.is_overridden {
+            continue;
+        }
+        let source = match &value.path {
+            Some(path) => format!("{} {}", value.source, path.display()),
+            None => value.source.to_string(),
+        };
+        println!(
+            "{} = {}\t# {source}",
+            value.name,
+            value.value.to_string().trim()
+        );
+    }
+    Ok(())
+}
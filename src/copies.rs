@@ -0,0 +1,184 @@
+use crate::{
+    error::{CustomError, Result},
+    trees::DiffTree,
+};
+use error_stack::ResultExt;
+use jj_lib::{
+    backend::{CommitId, CopyRecord, FileId},
+    commit::Commit,
+    copies::CopyRecords,
+    git_backend::GitBackend,
+    merged_tree::MergedTree,
+    object_id::ObjectId,
+    repo::Repo,
+    repo_path::RepoPathBuf,
+};
+
+/// Default minimum similarity for a removed and an added file to count as a rename, same as git.
+pub const DEFAULT_RENAME_SIMILARITY: f32 = 0.5;
+
+/// Detects renames and copies between two commits using the git backend's rename tracking.
+///
+/// Unlike `Store::get_copy_records`, this allows configuring the similarity threshold.
+/// Returns empty records if the repo isn't backed by git.
+pub fn get_copy_records(
+    root: &Commit,
+    head: &Commit,
+    similarity: f32,
+    repo: &impl Repo,
+) -> Result<CopyRecords> {
+    let Some(git_backend) = repo.store().backend_impl::<GitBackend>() else {
+        return Ok(CopyRecords::default());
+    };
+    let git_repo = git_backend.git_repo();
+    let root_tree = read_tree(&git_repo, root.id())?;
+    let head_tree = read_tree(&git_repo, head.id())?;
+    detect_copies(&root_tree, &head_tree, root.id(), head.id(), similarity)
+}
+
+/// Detects renames and copies between `from` rebased onto the parents of `to` and `to` itself,
+/// so that renames brought in by a moved base aren't attributed to the interdiff.
+///
+/// `rebased_tree` is the tree of the rebased commit. If the rebase has conflicts there is no git
+/// tree to compare, and copies are detected from `from` itself instead.
+pub fn get_rebased_copy_records(
+    from: &Commit,
+    rebased_tree: &MergedTree,
+    to: &Commit,
+    similarity: f32,
+    repo: &impl Repo,
+) -> Result<CopyRecords> {
+    let Some(rebased_tree_id) = rebased_tree.tree_ids().as_resolved() else {
+        return get_copy_records(from, to, similarity, repo);
+    };
+    let Some(git_backend) = repo.store().backend_impl::<GitBackend>() else {
+        return Ok(CopyRecords::default());
+    };
+    let git_repo = git_backend.git_repo();
+    let object_id = gix::ObjectId::try_from(rebased_tree_id.as_bytes())
+        .change_context(CustomError::RepoError)?;
+    let root_tree = git_repo
+        .find_tree(object_id)
+        .change_context(CustomError::RepoError)?;
+    let head_tree = read_tree(&git_repo, to.id())?;
+    detect_copies(&root_tree, &head_tree, from.id(), to.id(), similarity)
+}
+
+fn detect_copies(
+    root_tree: &gix::Tree<'_>,
+    head_tree: &gix::Tree<'_>,
+    root_id: &CommitId,
+    head_id: &CommitId,
+    similarity: f32,
+) -> Result<CopyRecords> {
+    let mut copy_records = CopyRecords::default();
+    let mut records = Vec::new();
+    root_tree
+        .changes()
+        .change_context(CustomError::RepoError)?
+        .options(|opts| {
+            opts.track_path().track_rewrites(Some(gix::diff::Rewrites {
+                copies: Some(gix::diff::rewrites::Copies {
+                    source: gix::diff::rewrites::CopySource::FromSetOfModifiedFiles,
+                    percentage: Some(similarity),
+                }),
+                percentage: Some(similarity),
+                limit: 1000,
+                track_empty: false,
+            }));
+        })
+        .for_each_to_obtain_tree(head_tree, |change| {
+            if let Some(record) = to_copy_record(change, root_id, head_id) {
+                records.push(Ok(record));
+            }
+            Ok::<_, std::convert::Infallible>(gix::object::tree::diff::Action::Continue)
+        })
+        .change_context(CustomError::RepoError)?;
+
+    copy_records
+        .add_records(records)
+        .change_context(CustomError::RepoError)?;
+    Ok(copy_records)
+}
+
+/// Detects renames and copies for the commits underlying a [`DiffTree`], where `from_tree` is
+/// the old side returned by [`DiffTree::get_trees`].
+pub fn get_tree_copy_records(
+    trees: &DiffTree,
+    from_tree: &MergedTree,
+    similarity: f32,
+    repo: &impl Repo,
+) -> Result<CopyRecords> {
+    match trees {
+        DiffTree::Interdiff { from, to } => {
+            get_rebased_copy_records(from, from_tree, to, similarity, repo)
+        }
+        // Merges are shown against their merged parents, for which there is no single tree
+        // to detect copies from
+        DiffTree::AddedCommit { commit } | DiffTree::RemovedCommit { commit }
+            if commit.parent_ids().len() > 1 =>
+        {
+            Ok(CopyRecords::default())
+        }
+        DiffTree::AddedCommit { commit } => match first_parent(commit)? {
+            Some(parent) => get_copy_records(&parent, commit, similarity, repo),
+            None => Ok(CopyRecords::default()),
+        },
+        DiffTree::RemovedCommit { commit } => match first_parent(commit)? {
+            Some(parent) => get_copy_records(commit, &parent, similarity, repo),
+            None => Ok(CopyRecords::default()),
+        },
+    }
+}
+
+fn first_parent(commit: &Commit) -> Result<Option<Commit>> {
+    commit
+        .parents()
+        .next()
+        .transpose()
+        .change_context(CustomError::CommitError(
+            "failed to get commit parents".to_string(),
+        ))
+}
+
+fn read_tree<'repo>(git_repo: &'repo gix::Repository, id: &CommitId) -> Result<gix::Tree<'repo>> {
+    let object_id =
+        gix::ObjectId::try_from(id.as_bytes()).change_context(CustomError::RepoError)?;
+    git_repo
+        .find_commit(object_id)
+        .change_context(CustomError::RepoError)?
+        .tree()
+        .change_context(CustomError::RepoError)
+}
+
+fn to_copy_record(
+    change: gix::object::tree::diff::Change,
+    root_id: &CommitId,
+    head_id: &CommitId,
+) -> Option<CopyRecord> {
+    let gix::object::tree::diff::Change::Rewrite {
+        source_location,
+        source_entry_mode,
+        source_id,
+        entry_mode,
+        location,
+        ..
+    } = change
+    else {
+        return None;
+    };
+    // jj can only represent copies of regular files
+    if !source_entry_mode.is_blob() || !entry_mode.is_blob() {
+        return None;
+    }
+    let source = RepoPathBuf::from_internal_string(str::from_utf8(source_location).ok()?).ok()?;
+    let target = RepoPathBuf::from_internal_string(str::from_utf8(location).ok()?).ok()?;
+
+    Some(CopyRecord {
+        target,
+        target_commit: head_id.clone(),
+        source,
+        source_file: FileId::from_bytes(source_id.as_bytes()),
+        source_commit: root_id.clone(),
+    })
+}
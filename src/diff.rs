@@ -1,22 +1,35 @@
 use crate::{
+    collapse::{CollapseThresholds, CollapsedFile, find_collapsed_files, render_collapsed_files},
+    copies::{self, DEFAULT_RENAME_SIMILARITY},
     error::{CustomError, Result},
+    external::{ExternalDiff, ExternalTool},
+    patch,
+    range_diff::render_range_diff,
     trees::DiffTree,
 };
-use error_stack::ResultExt;
+use error_stack::{Report, ResultExt};
+use futures::StreamExt;
 use jj_cli::{
-    diff_util::{self, DiffFormat, DiffRenderer, DiffStatOptions, UnifiedDiffOptions},
+    diff_util::{
+        self, ColorWordsDiffOptions, DiffFormat, DiffRenderer, DiffStatOptions, UnifiedDiffOptions,
+    },
     formatter::ColorFormatter,
     revset_util,
     ui::Ui,
 };
 use jj_lib::{
+    backend::Timestamp,
     commit::Commit,
     conflicts::ConflictMarkerStyle,
     copies::CopyRecords,
+    diff_presentation::LineCompareMode,
+    fileset::{self, FilesetDiagnostics, FilesetExpression},
     git_backend::GitBackend,
+    matchers::{DifferenceMatcher, EverythingMatcher, FilesMatcher, Matcher},
+    merged_tree::MergedTree,
     object_id::ObjectId,
     repo::Repo,
-    repo_path::RepoPathUiConverter,
+    repo_path::{RepoPathBuf, RepoPathUiConverter},
     revset::{
         self, Revset, RevsetDiagnostics, RevsetExtensions, RevsetIteratorExt, RevsetParseContext,
         RevsetWorkspaceContext, SymbolResolver, SymbolResolverExtension,
@@ -24,13 +37,16 @@ use jj_lib::{
     rewrite::rebase_to_dest_parent,
     workspace::Workspace,
 };
-use std::{collections::HashMap, fs::canonicalize, path::PathBuf};
+use serde::Serialize;
+use std::{collections::HashMap, fmt::Display, fs::canonicalize, path::PathBuf, str::FromStr};
 
 #[derive(Debug, Clone)]
 pub struct CommitDiff {
     pub from: Option<CommitMeta>,
     pub to: Option<CommitMeta>,
     pub stats: DiffStats,
+    /// Paths for which the old commit didn't rebase cleanly onto the new base
+    pub conflicts: Vec<String>,
 }
 
 impl CommitDiff {
@@ -38,24 +54,421 @@ impl CommitDiff {
         match (&self.from, &self.to) {
             (None, Some(_)) | (Some(_), None) => true,
             (Some(from), Some(to)) => {
-                (self.stats.changed_files > 0 || from.message != to.message) && from.sha != to.sha
+                (self.stats.changed_files > 0
+                    || from.message != to.message
+                    || !from.metadata_changes(to).is_empty())
+                    && from.sha != to.sha
+            }
+            (None, None) => false,
+        }
+    }
+
+    /// Whether the contents changed, ignoring the description and metadata.
+    pub fn has_code_changes(&self) -> bool {
+        match (&self.from, &self.to) {
+            (None, Some(_)) | (Some(_), None) => true,
+            (Some(from), Some(to)) => {
+                from.sha != to.sha && (self.stats.changed_files > 0 || !self.conflicts.is_empty())
             }
             (None, None) => false,
         }
     }
+
+    /// How the commit was matched between the two patchsets.
+    pub fn status(&self) -> CommitStatus {
+        match (&self.from, &self.to) {
+            (None, Some(_)) => CommitStatus::Added,
+            (Some(_), None) => CommitStatus::Removed,
+            (Some(from), Some(to)) if from.message != to.message => CommitStatus::Reworded,
+            _ if self.has_changes() => CommitStatus::Changed,
+            (Some(from), Some(to)) if from.sha != to.sha => CommitStatus::Rebased,
+            _ => CommitStatus::Unchanged,
+        }
+    }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CommitStatus {
+    /// Only in the new patchset
+    Added,
+    /// Only in the old patchset
+    Removed,
+    /// Changed description, and possibly contents or metadata
+    Reworded,
+    /// Changed contents or metadata
+    Changed,
+    /// Rewritten without any visible change, e.g. by a rebase
+    Rebased,
+    /// The very same commit in both patchsets
+    Unchanged,
+}
+
+impl Display for CommitStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Added => write!(f, "added"),
+            Self::Removed => write!(f, "removed"),
+            Self::Reworded => write!(f, "reworded"),
+            Self::Changed => write!(f, "changed"),
+            Self::Rebased => write!(f, "rebased"),
+            Self::Unchanged => write!(f, "unchanged"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
 pub struct CommitMeta {
     pub sha: String,
+    pub change_id: String,
     pub message: String,
+    /// Author as `Name <email>`
+    pub author: String,
+    pub author_date: String,
+    /// Committer as `Name <email>`, the commit date is left out since it changes on every rewrite
+    pub committer: String,
+    pub signed: bool,
+    pub parents: usize,
+}
+
+impl CommitMeta {
+    pub fn is_merge(&self) -> bool {
+        self.parents > 1
+    }
+
+    pub fn short_sha(&self) -> &str {
+        short_id(&self.sha)
+    }
+}
+
+/// Abbreviates a sha, or a patchset name which is usually one, for listings.
+pub fn short_id(id: &str) -> &str {
+    id.get(..8).unwrap_or(id)
+}
+
+impl CommitMeta {
+    pub fn from_commit(commit: &Commit) -> Self {
+        let author = commit.author();
+        let committer = commit.committer();
+        Self {
+            sha: commit.id().hex(),
+            change_id: commit.change_id().reverse_hex(),
+            message: commit.description().to_owned(),
+            author: format!("{} <{}>", author.name, author.email),
+            author_date: format_timestamp(&author.timestamp),
+            committer: format!("{} <{}>", committer.name, committer.email),
+            signed: commit.is_signed(),
+            parents: commit.parent_ids().len(),
+        }
+    }
+
+    /// Returns the changes to everything but the description and tree between two iterations
+    /// of a commit.
+    pub fn metadata_changes(&self, other: &CommitMeta) -> Vec<MetadataChange> {
+        let signature = |signed: bool| if signed { "signed" } else { "unsigned" }.to_string();
+        [
+            ("Author", self.author.clone(), other.author.clone()),
+            (
+                "Author date",
+                self.author_date.clone(),
+                other.author_date.clone(),
+            ),
+            ("Committer", self.committer.clone(), other.committer.clone()),
+            ("Signature", signature(self.signed), signature(other.signed)),
+            (
+                "Parents",
+                self.parents.to_string(),
+                other.parents.to_string(),
+            ),
+            ("Change ID", self.change_id.clone(), other.change_id.clone()),
+        ]
+        .into_iter()
+        .filter(|(_, old, new)| old != new)
+        .map(|(field, old, new)| MetadataChange { field, old, new })
+        .collect()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct MetadataChange {
+    pub field: &'static str,
+    pub old: String,
+    pub new: String,
+}
+
+fn format_timestamp(timestamp: &Timestamp) -> String {
+    let offset = chrono::FixedOffset::east_opt(timestamp.tz_offset * 60)
+        .unwrap_or_else(|| chrono::FixedOffset::east_opt(0).unwrap());
+    chrono::DateTime::from_timestamp_millis(timestamp.timestamp.0)
+        .map(|date| {
+            date.with_timezone(&offset)
+                .format("%Y-%m-%d %H:%M:%S %:z")
+                .to_string()
+        })
+        .unwrap_or_else(|| timestamp.timestamp.0.to_string())
+}
+
+#[derive(Debug, Clone)]
+pub struct DiffOptions {
+    /// Minimum similarity for renames and copies to be detected, `None` disables detection
+    pub rename_similarity: Option<f32>,
+    /// Only consider files matching this fileset, all files if `None`
+    pub filter: Option<FilesetExpression>,
+    /// How whitespace is compared, taken from the jj `diff.git` settings if `None`
+    pub whitespace: Option<LineCompareMode>,
+    /// Ignore hunks that only add or remove blank lines
+    pub ignore_blank_lines: bool,
+    pub format: RenderFormat,
+    /// Tool used for [`RenderFormat::External`]
+    pub external_tool: Option<ExternalTool>,
+    /// Summarize binary and large files instead of diffing them, `None` always shows the diff
+    pub collapse: Option<CollapseThresholds>,
+    /// Leave out merge commits that only bring in changes from trunk
+    pub hide_trunk_merges: bool,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum RenderFormat {
+    /// Unified git diff without colors
+    #[default]
+    Git,
+    /// Word-level diff with ANSI colors
+    ColorWords,
+    /// Diff of the old and new patch, like `git range-diff`
+    RangeDiff,
+    /// Output of an external diff tool, which may contain ANSI colors
+    External,
+}
+
+impl RenderFormat {
+    /// Returns the next format, skipping the external tool if none is configured.
+    pub fn toggle(self, with_external: bool) -> Self {
+        match self {
+            Self::Git => Self::ColorWords,
+            Self::ColorWords => Self::RangeDiff,
+            Self::RangeDiff if with_external => Self::External,
+            Self::RangeDiff | Self::External => Self::Git,
+        }
+    }
+}
+
+impl FromStr for RenderFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s {
+            "git" => Ok(Self::Git),
+            "color-words" => Ok(Self::ColorWords),
+            "range-diff" => Ok(Self::RangeDiff),
+            "external" => Ok(Self::External),
+            _ => Err(format!("unknown diff format: {s}")),
+        }
+    }
+}
+
+impl Display for RenderFormat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Git => write!(f, "git"),
+            Self::ColorWords => write!(f, "color-words"),
+            Self::RangeDiff => write!(f, "range-diff"),
+            Self::External => write!(f, "external"),
+        }
+    }
+}
+
+impl Default for DiffOptions {
+    fn default() -> Self {
+        Self {
+            rename_similarity: Some(DEFAULT_RENAME_SIMILARITY),
+            filter: None,
+            whitespace: None,
+            ignore_blank_lines: false,
+            format: RenderFormat::default(),
+            external_tool: None,
+            collapse: Some(CollapseThresholds::default()),
+            hide_trunk_merges: false,
+        }
+    }
+}
+
+impl DiffOptions {
+    pub fn without_filter(&self) -> Self {
+        Self {
+            filter: None,
+            ..self.clone()
+        }
+    }
+
+    /// Options that don't hide any change, for patches and checks that must be complete: no
+    /// filter, exact whitespace and blank lines, no collapsed files and no hidden merges.
+    pub fn exact(&self) -> Self {
+        Self {
+            whitespace: Some(LineCompareMode::Exact),
+            ignore_blank_lines: false,
+            collapse: None,
+            hide_trunk_merges: false,
+            ..self.without_filter()
+        }
+    }
+
+    fn unified_diff_options(&self, workspace: &Workspace) -> Result<UnifiedDiffOptions> {
+        let mut unified_diff_options = UnifiedDiffOptions::from_settings(workspace.settings())
+            .change_context(CustomError::ConfigError)?;
+        if let Some(compare_mode) = self.whitespace {
+            unified_diff_options.line_diff.compare_mode = compare_mode;
+        }
+        Ok(unified_diff_options)
+    }
+
+    fn diff_format(&self, workspace: &Workspace) -> Result<DiffFormat> {
+        Ok(match self.format {
+            RenderFormat::Git | RenderFormat::RangeDiff | RenderFormat::External => {
+                DiffFormat::Git(Box::new(self.unified_diff_options(workspace)?))
+            }
+            RenderFormat::ColorWords => {
+                let mut color_words_options =
+                    ColorWordsDiffOptions::from_settings(workspace.settings())
+                        .change_context(CustomError::ConfigError)?;
+                if let Some(compare_mode) = self.whitespace {
+                    color_words_options.line_diff.compare_mode = compare_mode;
+                }
+                DiffFormat::ColorWords(Box::new(color_words_options))
+            }
+        })
+    }
+
+    fn diff_stat_options(&self, workspace: &Workspace) -> Result<DiffStatOptions> {
+        Ok(DiffStatOptions {
+            line_diff: self.unified_diff_options(workspace)?.line_diff,
+        })
+    }
+
+    fn ignores_whitespace(&self) -> bool {
+        !matches!(self.whitespace, None | Some(LineCompareMode::Exact))
+    }
+
+    pub(crate) fn matcher(&self) -> Box<dyn Matcher> {
+        self.filter
+            .as_ref()
+            .map(FilesetExpression::to_matcher)
+            .unwrap_or_else(|| Box::new(EverythingMatcher))
+    }
+
+    pub(crate) fn copy_records(
+        &self,
+        root: &Commit,
+        head: &Commit,
+        repo: &impl Repo,
+    ) -> Result<CopyRecords> {
+        match self.rename_similarity {
+            Some(similarity) => copies::get_copy_records(root, head, similarity, repo),
+            None => Ok(CopyRecords::default()),
+        }
+    }
+
+    /// Copy records between the old commit rebased onto the new one's parents and the new commit.
+    fn rebased_copy_records(
+        &self,
+        from: &Commit,
+        rebased_tree: &MergedTree,
+        to: &Commit,
+        repo: &impl Repo,
+    ) -> Result<CopyRecords> {
+        match self.rename_similarity {
+            Some(similarity) => {
+                copies::get_rebased_copy_records(from, rebased_tree, to, similarity, repo)
+            }
+            None => Ok(CopyRecords::default()),
+        }
+    }
+
+    fn tree_copy_records(
+        &self,
+        trees: &DiffTree,
+        from_tree: &MergedTree,
+        repo: &impl Repo,
+    ) -> Result<CopyRecords> {
+        match self.rename_similarity {
+            Some(similarity) => copies::get_tree_copy_records(trees, from_tree, similarity, repo),
+            None => Ok(CopyRecords::default()),
+        }
+    }
 }
 
-#[derive(Debug, Default, Copy, Clone)]
+/// Parses a fileset expression like `src/ ~ glob:"**/*.lock"` relative to the current directory.
+pub fn parse_filter(expr: &str, workspace: &Workspace) -> Result<FilesetExpression> {
+    let path_converter = path_converter(workspace)?;
+    fileset::parse(&mut FilesetDiagnostics::new(), expr, &path_converter)
+        .change_context(CustomError::ExprError)
+        .attach(format!("invalid fileset expression: {expr}"))
+}
+
+fn path_converter(workspace: &Workspace) -> Result<RepoPathUiConverter> {
+    let cwd = canonicalize(PathBuf::from(".")).change_context(CustomError::RepoError)?;
+    Ok(RepoPathUiConverter::Fs {
+        cwd,
+        base: workspace.workspace_root().to_owned(),
+    })
+}
+
+#[derive(Debug, Default, Clone, Serialize)]
 pub struct DiffStats {
     pub additions: usize,
     pub removals: usize,
     pub changed_files: usize,
+    /// Counts of the individual files, in diff order
+    pub files: Vec<FileStats>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct FileStats {
+    pub path: String,
+    pub additions: usize,
+    pub removals: usize,
+    /// Binary files have no line counts
+    pub binary: bool,
+}
+
+impl DiffStats {
+    /// Counts the changes in a rendered git diff.
+    pub fn from_patch(diff: &str) -> Self {
+        let lines: Vec<&str> = diff.lines().collect();
+        let files = patch::parse(diff);
+        let files: Vec<FileStats> = files
+            .iter()
+            .enumerate()
+            .map(|(index, file)| {
+                let end = files.get(index + 1).map_or(lines.len(), |next| next.start);
+                let binary = lines[file.start..end]
+                    .iter()
+                    .any(|line| line.starts_with("Binary files ") || *line == "GIT binary patch");
+                let (additions, removals) = file
+                    .hunks
+                    .iter()
+                    .flat_map(|hunk| hunk.changed_lines())
+                    .fold((0, 0), |(additions, removals), line| {
+                        if line.starts_with('+') {
+                            (additions + 1, removals)
+                        } else {
+                            (additions, removals + 1)
+                        }
+                    });
+                FileStats {
+                    path: file.path.to_string(),
+                    additions,
+                    removals,
+                    binary,
+                }
+            })
+            .collect();
+        Self {
+            additions: files.iter().map(|file| file.additions).sum(),
+            removals: files.iter().map(|file| file.removals).sum(),
+            changed_files: files.len(),
+            files,
+        }
+    }
 }
 
 fn evaluate_revset_expr<'a>(
@@ -65,7 +478,7 @@ fn evaluate_revset_expr<'a>(
 ) -> Result<Box<dyn Revset + 'a>> {
     let aliases_map = &revset_util::load_revset_aliases(&Ui::null(), workspace.settings().config())
         .map_err(|_| CustomError::RepoError)?;
-    let cwd = canonicalize(PathBuf::from(".")).change_context(CustomError::RepoError)?;
+    let path_converter = path_converter(workspace)?;
     let context = RevsetParseContext {
         aliases_map,
         local_variables: HashMap::new(),
@@ -75,10 +488,7 @@ fn evaluate_revset_expr<'a>(
         use_glob_by_default: false,
         extensions: &RevsetExtensions::default(),
         workspace: Some(RevsetWorkspaceContext {
-            path_converter: &RepoPathUiConverter::Fs {
-                cwd,
-                base: workspace.workspace_root().to_owned(),
-            },
+            path_converter: &path_converter,
             workspace_name: workspace.workspace_name(),
         }),
     };
@@ -152,18 +562,34 @@ impl DiffSource {
     }
 }
 
+/// Counts the commits of a branch that aren't on trunk.
+pub fn count_branch_commits(
+    branch: &str,
+    workspace: &Workspace,
+    repo: &impl Repo,
+) -> Result<usize> {
+    Ok(get_commits(&format!("::{branch} ~ ::trunk()"), workspace, repo)?.len())
+}
+
+/// Merge commits with a parent on trunk, i.e. merges of trunk into the branch
+const TRUNK_MERGES_EXPR: &str = "(merges() & children(::trunk()))";
+
 pub fn calculate_branch_diff(
     from_branch: &str,
     to_branch: &str,
     workspace: &Workspace,
     repo: &impl Repo,
+    options: &DiffOptions,
 ) -> Result<Vec<CommitDiff>> {
     let fork_point_expr = format!("fork_point({} | {} | trunk())", from_branch, to_branch);
 
-    let from_expr = format!("{}..{}", fork_point_expr, from_branch);
+    let mut from_expr = format!("{}..{}", fork_point_expr, from_branch);
+    let mut to_expr = format!("::{} ~ ::trunk()", to_branch);
+    if options.hide_trunk_merges {
+        from_expr = format!("({from_expr}) ~ {TRUNK_MERGES_EXPR}");
+        to_expr = format!("({to_expr}) ~ {TRUNK_MERGES_EXPR}");
+    }
     let from_commits = get_commits(&from_expr, workspace, repo)?;
-
-    let to_expr = format!("::{} ~ ::trunk()", to_branch);
     let to_commits = get_commits(&to_expr, workspace, repo)?;
 
     let from_sources = from_commits
@@ -222,45 +648,108 @@ pub fn calculate_branch_diff(
         let from_commit = from_map.get(change_id);
         let to_commit = to_map.get(change_id);
 
-        let from_meta = from_commit.map(|c| CommitMeta {
-            sha: c.id().hex(),
-            message: c.description().to_owned(),
-        });
+        let from_meta = from_commit.map(|c| CommitMeta::from_commit(c));
+        let to_meta = to_commit.map(|c| CommitMeta::from_commit(c));
 
-        let to_meta = to_commit.map(|c| CommitMeta {
-            sha: c.id().hex(),
-            message: c.description().to_owned(),
-        });
-
-        let stats = match (from_commit, to_commit) {
-            (Some(from), Some(to)) if from.id() == to.id() => calculate_commit_stats(to, repo),
-            (Some(from), Some(to)) => calculate_diff_stats(from, to, repo),
-            (Some(from), None) => calculate_commit_stats(from, repo),
-            (None, Some(to)) => calculate_commit_stats(to, repo),
-            (None, None) => Ok(DiffStats::default()),
-        }
-        .change_context(CustomError::RepoError)?;
+        let (stats, conflicts) = match (from_commit, to_commit) {
+            (Some(from), Some(to)) if from.id() == to.id() => (
+                calculate_commit_stats(to, workspace, repo, options),
+                Vec::new(),
+            ),
+            (Some(from), Some(to)) => {
+                let from_tree = rebase_to_dest_parent(repo, std::slice::from_ref(*from), to)
+                    .change_context(CustomError::RepoError)?;
+                let to_tree = to.tree();
+                let conflicts = get_rebase_conflicts(&from_tree, &to_tree, options)?
+                    .iter()
+                    .map(|path| path.as_internal_file_string().to_owned())
+                    .collect();
+                let copy_records = options.rebased_copy_records(from, &from_tree, to, repo)?;
+                let stats = calculate_tree_stats(
+                    &from_tree,
+                    &to_tree,
+                    &copy_records,
+                    workspace,
+                    repo,
+                    options,
+                );
+                (stats, conflicts)
+            }
+            (Some(from), None) => (
+                calculate_commit_stats(from, workspace, repo, options),
+                Vec::new(),
+            ),
+            (None, Some(to)) => (
+                calculate_commit_stats(to, workspace, repo, options),
+                Vec::new(),
+            ),
+            (None, None) => (Ok(DiffStats::default()), Vec::new()),
+        };
+        let stats = stats.change_context(CustomError::RepoError)?;
 
         commit_diffs.push(CommitDiff {
             from: from_meta,
             to: to_meta,
             stats,
+            conflicts,
         });
     }
 
     Ok(commit_diffs)
 }
 
-fn calculate_diff_stats(from: &Commit, to: &Commit, repo: &impl Repo) -> Result<DiffStats> {
+fn calculate_diff_stats(
+    from: &Commit,
+    to: &Commit,
+    workspace: &Workspace,
+    repo: &impl Repo,
+    options: &DiffOptions,
+) -> Result<DiffStats> {
     let from_tree = rebase_to_dest_parent(repo, std::slice::from_ref(from), to)
         .change_context(CustomError::RepoError)?;
-    let to_tree = to.tree();
+    let copy_records = options.rebased_copy_records(from, &from_tree, to, repo)?;
+    calculate_tree_stats(
+        &from_tree,
+        &to.tree(),
+        &copy_records,
+        workspace,
+        repo,
+        options,
+    )
+}
+
+fn calculate_tree_stats(
+    from_tree: &MergedTree,
+    to_tree: &MergedTree,
+    copy_records: &CopyRecords,
+    workspace: &Workspace,
+    repo: &impl Repo,
+    options: &DiffOptions,
+) -> Result<DiffStats> {
+    let matcher = options.matcher();
 
-    let matcher = jj_lib::matchers::EverythingMatcher;
-    let copy_records = CopyRecords::default();
-    let diff_stream = from_tree.diff_stream_with_copies(&to_tree, &matcher, &copy_records);
+    // jj has no notion of blank line changes, so count what is left of the rendered diff instead
+    if options.ignore_blank_lines {
+        let diff = render_tree_diff(
+            from_tree,
+            to_tree,
+            &matcher,
+            ConflictMarkerStyle::Git,
+            copy_records,
+            &DiffOptions {
+                format: RenderFormat::Git,
+                ..options.clone()
+            },
+            workspace,
+            repo,
+            u16::MAX,
+        )?;
+        return Ok(DiffStats::from_patch(&diff));
+    }
 
-    let diff_stat_options = DiffStatOptions::default();
+    let diff_stream = from_tree.diff_stream_with_copies(to_tree, &matcher, copy_records);
+
+    let diff_stat_options = options.diff_stat_options(workspace)?;
 
     let stats = futures::executor::block_on(diff_util::DiffStats::calculate(
         repo.store(),
@@ -272,14 +761,42 @@ fn calculate_diff_stats(from: &Commit, to: &Commit, repo: &impl Repo) -> Result<
         "couldn't block on future".to_owned(),
     ))?;
 
+    // Files whose only changes were whitespace still show up as entries, binary files have no
+    // line counts and always changed
+    let files: Vec<FileStats> = stats
+        .entries()
+        .iter()
+        .filter(|entry| {
+            !options.ignores_whitespace()
+                || entry
+                    .added_removed
+                    .is_none_or(|(added, removed)| added > 0 || removed > 0)
+        })
+        .map(|entry| {
+            let (additions, removals) = entry.added_removed.unwrap_or_default();
+            FileStats {
+                path: entry.path.target().as_internal_file_string().to_owned(),
+                additions,
+                removals,
+                binary: entry.added_removed.is_none(),
+            }
+        })
+        .collect();
+
     Ok(DiffStats {
         additions: stats.count_total_added(),
         removals: stats.count_total_removed(),
-        changed_files: stats.entries().len(),
+        changed_files: files.len(),
+        files,
     })
 }
 
-fn calculate_commit_stats(commit: &Commit, repo: &impl Repo) -> Result<DiffStats> {
+fn calculate_commit_stats(
+    commit: &Commit,
+    workspace: &Workspace,
+    repo: &impl Repo,
+    options: &DiffOptions,
+) -> Result<DiffStats> {
     let parents: Vec<Commit> = commit
         .parents()
         .collect::<std::result::Result<Vec<_>, _>>()
@@ -287,12 +804,106 @@ fn calculate_commit_stats(commit: &Commit, repo: &impl Repo) -> Result<DiffStats
             "failed to get commit parents".to_string(),
         ))?;
 
-    if parents.is_empty() {
-        return Ok(DiffStats::default());
+    match parents.as_slice() {
+        [] => Ok(DiffStats::default()),
+        [parent] => calculate_diff_stats(parent, commit, workspace, repo, options),
+        // For merges only the conflict resolution counts, i.e. what differs from the
+        // automatically merged parents. Copies relative to a single parent would be misleading.
+        _ => {
+            let merged_parents = commit
+                .parent_tree(repo)
+                .change_context(CustomError::RepoError)?;
+            calculate_tree_stats(
+                &merged_parents,
+                &commit.tree(),
+                &CopyRecords::default(),
+                workspace,
+                repo,
+                options,
+            )
+        }
+    }
+}
+
+/// Returns the paths that are conflicted in `from_tree` but not in `to_tree`, i.e. the files for
+/// which the old commit didn't rebase cleanly onto the new base.
+pub fn get_rebase_conflicts(
+    from_tree: &MergedTree,
+    to_tree: &MergedTree,
+    options: &DiffOptions,
+) -> Result<Vec<RepoPathBuf>> {
+    let matcher = options.matcher();
+    let mut conflicts = Vec::new();
+    for (path, _) in from_tree.conflicts() {
+        if !matcher.matches(&path) {
+            continue;
+        }
+        let to_value = to_tree
+            .path_value(&path)
+            .change_context(CustomError::RepoError)?;
+        if to_value.is_resolved() {
+            conflicts.push(path);
+        }
+    }
+    Ok(conflicts)
+}
+
+#[derive(Debug, Clone)]
+pub struct RenderedInterdiff {
+    /// Changed author, committer, signature and similar fields, if any
+    pub metadata_diff: Option<String>,
+    /// Line diff of the commit description, if it changed
+    pub message_diff: Option<String>,
+    pub diff: String,
+    /// Files whose interdiff is only approximate because the rebase produced conflicts
+    pub conflicts: Vec<String>,
+    /// Binary or large files that were left out of `diff` and only summarized
+    pub collapsed: Vec<CollapsedFile>,
+}
+
+impl RenderedInterdiff {
+    /// The titled sections shown before the diff: changed metadata, the description diff and
+    /// the summaries of collapsed files.
+    pub fn sections(&self) -> Vec<(&'static str, String)> {
+        let collapsed =
+            (!self.collapsed.is_empty()).then(|| render_collapsed_files(&self.collapsed));
+        [
+            ("Metadata", self.metadata_diff.clone()),
+            ("Commit message", self.message_diff.clone()),
+            ("Collapsed files", collapsed),
+        ]
+        .into_iter()
+        .filter_map(|(title, section)| Some((title, section?)))
+        .collect()
+    }
+}
+
+/// Renders the changed metadata fields of an interdiff as `-Field: old` and `+Field: new` lines.
+pub fn render_metadata_diff(trees: &DiffTree) -> Option<String> {
+    let DiffTree::Interdiff { from, to } = trees else {
+        return None;
+    };
+    let changes = CommitMeta::from_commit(from).metadata_changes(&CommitMeta::from_commit(to));
+    if changes.is_empty() {
+        return None;
     }
+    let mut diff = String::new();
+    for change in changes {
+        diff.push_str(&format!("-{}: {}\n", change.field, change.old));
+        diff.push_str(&format!("+{}: {}\n", change.field, change.new));
+    }
+    Some(diff)
+}
 
-    let parent = &parents[0];
-    calculate_diff_stats(parent, commit, repo)
+/// Renders the changes to the commit description as lines prefixed with ` `, `-` or `+`.
+pub fn render_message_diff(trees: &DiffTree) -> Option<String> {
+    let (from, to) = trees.get_descriptions()?;
+    let mut diff = String::new();
+    for line in patch::diff_lines(from, to) {
+        diff.push_str(&line);
+        diff.push('\n');
+    }
+    Some(diff)
 }
 
 pub fn render_interdiff(
@@ -300,35 +911,195 @@ pub fn render_interdiff(
     workspace: &Workspace,
     repo: &impl Repo,
     width: u16,
-) -> Result<String> {
+    options: &DiffOptions,
+) -> Result<RenderedInterdiff> {
+    let metadata_diff = render_metadata_diff(trees);
+    if options.format == RenderFormat::RangeDiff {
+        // The range-diff already covers the description
+        return Ok(RenderedInterdiff {
+            metadata_diff,
+            message_diff: None,
+            diff: render_range_diff(trees, workspace, repo, width, options)?,
+            conflicts: Vec::new(),
+            collapsed: Vec::new(),
+        });
+    }
+
+    let message_diff = render_message_diff(trees);
     let (from_tree, to_tree) = trees.get_trees(repo)?;
+    let conflicts = get_rebase_conflicts(&from_tree, &to_tree, options)?;
+    if options.format == RenderFormat::External {
+        let tool = options.external_tool.clone().ok_or_else(|| {
+            Report::new(CustomError::ConfigError).attach("no external diff tool configured")
+        })?;
+        return Ok(RenderedInterdiff {
+            metadata_diff,
+            message_diff,
+            diff: ExternalDiff::new(tool, trees, repo, options)?.capture(width)?,
+            conflicts: conflicts
+                .iter()
+                .map(|path| path.as_internal_file_string().to_owned())
+                .collect(),
+            collapsed: Vec::new(),
+        });
+    }
 
-    let matcher = jj_lib::matchers::EverythingMatcher;
+    let copy_records = options.tree_copy_records(trees, &from_tree, repo)?;
+    let collapsed = match &options.collapse {
+        Some(thresholds) => find_collapsed_files(
+            &from_tree,
+            &to_tree,
+            options.matcher().as_ref(),
+            thresholds,
+            repo,
+        )?,
+        None => Vec::new(),
+    };
+    let matcher: Box<dyn Matcher> = if collapsed.is_empty() {
+        options.matcher()
+    } else {
+        Box::new(DifferenceMatcher::new(
+            options.matcher(),
+            FilesMatcher::new(collapsed.iter().map(|file| &file.path)),
+        ))
+    };
 
-    let cwd = canonicalize(PathBuf::from(".")).change_context(CustomError::RepoError)?;
-    let repo_path_converter = RepoPathUiConverter::Fs {
-        cwd,
-        base: workspace.workspace_root().to_owned(),
+    if conflicts.is_empty() {
+        let diff = render_tree_diff(
+            &from_tree,
+            &to_tree,
+            &matcher,
+            ConflictMarkerStyle::Git,
+            &copy_records,
+            options,
+            workspace,
+            repo,
+            width,
+        )?;
+        return Ok(RenderedInterdiff {
+            metadata_diff,
+            message_diff,
+            diff,
+            conflicts: Vec::new(),
+            collapsed,
+        });
+    }
+
+    // Conflicted files are shown with every side spelled out, so that the markers can't be
+    // mistaken for part of the code
+    let conflict_matcher = FilesMatcher::new(&conflicts);
+    let mut diff = render_tree_diff(
+        &from_tree,
+        &to_tree,
+        &conflict_matcher,
+        ConflictMarkerStyle::Snapshot,
+        &copy_records,
+        options,
+        workspace,
+        repo,
+        width,
+    )?;
+    diff.push_str(&render_tree_diff(
+        &from_tree,
+        &to_tree,
+        &DifferenceMatcher::new(matcher, conflict_matcher),
+        ConflictMarkerStyle::Git,
+        &copy_records,
+        options,
+        workspace,
+        repo,
+        width,
+    )?);
+
+    Ok(RenderedInterdiff {
+        metadata_diff,
+        message_diff,
+        diff,
+        conflicts: conflicts
+            .iter()
+            .map(|path| path.as_internal_file_string().to_owned())
+            .collect(),
+        collapsed,
+    })
+}
+
+/// Renders the changes between the old and new base of an interdiff, limited to the files
+/// touched by the interdiff itself.
+pub fn render_upstream_diff(
+    trees: &DiffTree,
+    workspace: &Workspace,
+    repo: &impl Repo,
+    width: u16,
+    options: &DiffOptions,
+) -> Result<Option<String>> {
+    let Some((from_base, to_base)) = trees.get_base_trees(repo)? else {
+        return Ok(None);
     };
+    let (from_tree, to_tree) = trees.get_trees(repo)?;
+
+    let paths = futures::executor::block_on(
+        from_tree
+            .diff_stream(&to_tree, &options.matcher())
+            .map(|entry| entry.path)
+            .collect::<Vec<_>>(),
+    );
+    if paths.is_empty() {
+        return Ok(None);
+    }
+    let matcher = FilesMatcher::new(paths);
+
+    render_tree_diff(
+        &from_base,
+        &to_base,
+        &matcher,
+        ConflictMarkerStyle::Git,
+        &CopyRecords::default(),
+        options,
+        workspace,
+        repo,
+        width,
+    )
+    // The base moved, but none of the upstream changes touch the PR's files
+    .map(|diff| (!diff.is_empty()).then_some(diff))
+}
+
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn render_tree_diff(
+    from_tree: &MergedTree,
+    to_tree: &MergedTree,
+    matcher: &dyn Matcher,
+    conflict_marker_style: ConflictMarkerStyle,
+    copy_records: &CopyRecords,
+    options: &DiffOptions,
+    workspace: &Workspace,
+    repo: &impl Repo,
+    width: u16,
+) -> Result<String> {
+    let repo_path_converter = path_converter(workspace)?;
     let renderer = DiffRenderer::new(
         repo,
         &repo_path_converter,
-        ConflictMarkerStyle::Git,
-        vec![DiffFormat::Git(Box::new(
-            UnifiedDiffOptions::from_settings(workspace.settings())
-                .change_context(CustomError::ConfigError)?,
-        ))],
+        conflict_marker_style,
+        vec![options.diff_format(workspace)?],
     );
 
-    let copy_records = CopyRecords::default();
     let mut diff = Vec::new();
-    let mut formatter = ColorFormatter::new(&mut diff, Vec::new().into(), false);
+    // Only word-level diffs need colors, git diffs are highlighted by their line prefix
+    let mut formatter = match options.format {
+        RenderFormat::Git | RenderFormat::RangeDiff | RenderFormat::External => {
+            ColorFormatter::new(&mut diff, Vec::new().into(), false)
+        }
+        RenderFormat::ColorWords => {
+            ColorFormatter::for_config(&mut diff, workspace.settings().config(), false)
+                .change_context(CustomError::ConfigError)?
+        }
+    };
     futures::executor::block_on(renderer.show_diff(
         &Ui::null(),
         &mut formatter,
-        jj_lib::merge::Diff::new(&from_tree, &to_tree),
-        &matcher,
-        &copy_records,
+        jj_lib::merge::Diff::new(from_tree, to_tree),
+        matcher,
+        copy_records,
         width.into(),
     ))
     .change_context(CustomError::ProcessError(
@@ -336,7 +1107,13 @@ pub fn render_interdiff(
     ))?;
 
     drop(formatter);
-    String::from_utf8(diff).change_context(CustomError::ProcessError(
+    let diff = String::from_utf8(diff).change_context(CustomError::ProcessError(
         "failed to parse diff output as UTF-8".to_owned(),
-    ))
+    ))?;
+
+    if options.ignore_blank_lines && options.format != RenderFormat::ColorWords {
+        Ok(patch::strip_blank_line_hunks(&diff))
+    } else {
+        Ok(diff)
+    }
 }
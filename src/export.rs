@@ -0,0 +1,169 @@
+use std::{fs, path::PathBuf, sync::Arc};
+
+use clap::ValueEnum;
+use diffsoup::{
+    diff::{
+        CommitDiff, CommitStatus, DiffOptions, RenderFormat, RenderedInterdiff, get_commit,
+        render_interdiff,
+    },
+    mbox::{format_cover_letter, format_patch, patch_file_name},
+    pr::PrFetcher,
+    trees::DiffTree,
+};
+use jj_lib::{repo::ReadonlyRepo, workspace::Workspace};
+
+use crate::{
+    DiffArgs,
+    show::{self, BranchComparison, PatchsetArgs},
+};
+
+#[derive(clap::Args, Debug)]
+pub struct ExportArgs {
+    #[command(flatten)]
+    pub diff: DiffArgs,
+
+    #[command(flatten)]
+    patchsets: PatchsetArgs,
+
+    /// Directory to write the patch files to, created if it doesn't exist
+    #[arg(short, long, value_name = "DIR")]
+    output: PathBuf,
+
+    /// What to export
+    #[arg(long, value_enum, default_value_t = ExportMode::Series)]
+    mode: ExportMode,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum ExportMode {
+    /// Every commit of the newer patchset against its parent
+    Series,
+    /// The interdiff of every commit that changed between the two patchsets
+    Interdiff,
+}
+
+/// Writes a cover letter and one `git format-patch` style mbox file per commit into the output
+/// directory.
+pub fn run(
+    workspace: Workspace,
+    repo: Arc<ReadonlyRepo>,
+    pr_fetcher: Box<dyn PrFetcher>,
+    options: DiffOptions,
+    args: &ExportArgs,
+) -> Result<(), Box<dyn std::error::Error>> {
+    // Patches have to be applicable, so they must be git diffs of every change
+    let options = DiffOptions {
+        format: RenderFormat::Git,
+        ..options.exact()
+    };
+    let BranchComparison {
+        history,
+        from_index,
+        to_index,
+        repo,
+        commits,
+    } = args
+        .patchsets
+        .compare(&workspace, repo, pr_fetcher.as_ref(), &options)?;
+
+    // Commits are listed newest first, patches are numbered oldest first
+    let commits: Vec<&CommitDiff> = commits
+        .iter()
+        .rev()
+        .filter(|commit| match args.mode {
+            ExportMode::Series => commit.to.is_some(),
+            ExportMode::Interdiff => commit.to.is_some() && commit.has_changes(),
+        })
+        .collect();
+
+    let base = format!("{} ({})", from_index + 1, history[from_index].name.as_str());
+    let mut patches = Vec::new();
+    let mut skipped_merges = Vec::new();
+    for commit in &commits {
+        let Some(meta) = &commit.to else {
+            continue;
+        };
+        if meta.is_merge() {
+            eprintln!("Skipping merge commit {}", meta.sha);
+            skipped_merges.push(meta);
+            continue;
+        }
+        let (diff, notes) = match args.mode {
+            ExportMode::Series => {
+                let to_commit = get_commit(&meta.sha, &workspace, repo.as_ref())?;
+                let tree = DiffTree::AddedCommit { commit: &to_commit };
+                let interdiff = render_interdiff(&tree, &workspace, repo.as_ref(), 80, &options)?;
+                (interdiff.diff, None)
+            }
+            ExportMode::Interdiff => {
+                let Some((_, interdiff)) =
+                    show::render(commit, &workspace, repo.as_ref(), 80, &options)?
+                else {
+                    continue;
+                };
+                let notes = interdiff_notes(commit, &base, &interdiff);
+                (interdiff.diff, Some(notes))
+            }
+        };
+        patches.push((meta, diff, notes));
+    }
+
+    let total = patches.len();
+    let subject_prefix = |number: usize| match args.mode {
+        ExportMode::Series => format!("PATCH {number}/{total}"),
+        ExportMode::Interdiff => format!("PATCH interdiff {number}/{total}"),
+    };
+    let target = format!("{} ({})", to_index + 1, history[to_index].name.as_str());
+    let (title, introduction) = match args.mode {
+        ExportMode::Series => (
+            format!("Patchset {target}"),
+            format!("The commits of patchset {target}:"),
+        ),
+        ExportMode::Interdiff => (
+            format!("Changes from patchset {base} to {target}"),
+            format!("The interdiffs of the commits that changed since patchset {base}:"),
+        ),
+    };
+    let settings = workspace.settings();
+    let sender = format!("{} <{}>", settings.user_name(), settings.user_email());
+    let metas: Vec<_> = patches.iter().map(|(meta, _, _)| *meta).collect();
+    let mut files = vec![(
+        "0000-cover-letter.patch".to_string(),
+        format_cover_letter(
+            &sender,
+            &format!("[{}] {title}", subject_prefix(0)),
+            &introduction,
+            &metas,
+            &skipped_merges,
+        ),
+    )];
+    // Format everything first, so that no partial series is written if a patch is refused
+    for (index, (meta, diff, notes)) in patches.into_iter().enumerate() {
+        let number = index + 1;
+        let patch = format_patch(meta, &subject_prefix(number), notes.as_deref(), &diff)?;
+        files.push((patch_file_name(number, meta), patch));
+    }
+
+    fs::create_dir_all(&args.output)?;
+    for (name, contents) in files {
+        let path = args.output.join(name);
+        fs::write(&path, contents)?;
+        println!("{}", path.display());
+    }
+    Ok(())
+}
+
+/// Describes what the interdiff is relative to, along with changes to the description and
+/// metadata which the diff itself doesn't show.
+fn interdiff_notes(commit: &CommitDiff, base: &str, interdiff: &RenderedInterdiff) -> String {
+    let mut notes = match (commit.status(), &commit.from) {
+        (CommitStatus::Changed | CommitStatus::Reworded, Some(from)) => {
+            format!("Interdiff against {} from patchset {base}\n", from.sha)
+        }
+        _ => format!("New commit since patchset {base}\n"),
+    };
+    for (title, section) in interdiff.sections() {
+        notes.push_str(&format!("\n{title}:\n{section}"));
+    }
+    notes
+}
@@ -0,0 +1,253 @@
+use crate::{
+    diff::DiffOptions,
+    error::{CustomError, Result},
+    trees::DiffTree,
+};
+use error_stack::{Report, ResultExt};
+use futures::StreamExt;
+use jj_lib::{
+    backend::TreeValue, file_util::copy_async_to_sync, matchers::Matcher, merge::MergedTreeValue,
+    merged_tree::MergedTree, repo::Repo, repo_path::RepoPath, settings::UserSettings,
+};
+use std::{
+    fs,
+    path::Path,
+    process::{Command, Stdio},
+};
+use temp_dir::TempDir;
+
+/// A diff tool that compares two directories, configured the same way as for jj.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExternalTool {
+    pub program: String,
+    /// Arguments, where `$left`, `$right` and `$width` are substituted
+    pub args: Vec<String>,
+}
+
+impl ExternalTool {
+    /// Looks up the tool `name` in the `merge-tools` config table, or uses jj's
+    /// `ui.diff-formatter` if no name is given.
+    ///
+    /// Returns `None` if the configured diff formatter is one of jj's builtin formats like `:git`.
+    pub fn from_settings(settings: &UserSettings, name: Option<&str>) -> Result<Option<Self>> {
+        if let Some(name) = name {
+            return Ok(Some(Self::by_name(settings, name)));
+        }
+        if let Ok(name) = settings.get_string("ui.diff-formatter") {
+            if name.starts_with(':') {
+                return Ok(None);
+            }
+            return Ok(Some(Self::by_name(settings, &name)));
+        }
+        let Ok(command) = settings.get::<Vec<String>>("ui.diff-formatter") else {
+            return Ok(None);
+        };
+        match command.split_first() {
+            Some((program, args)) => Ok(Some(Self {
+                program: program.clone(),
+                args: args.to_vec(),
+            })),
+            None => Err(Report::new(CustomError::ConfigError)
+                .attach("ui.diff-formatter must not be an empty command")),
+        }
+    }
+
+    fn by_name(settings: &UserSettings, name: &str) -> Self {
+        let program = settings
+            .get_string(["merge-tools", name, "program"])
+            .unwrap_or_else(|_| name.to_string());
+        let args = settings
+            .get::<Vec<String>>(["merge-tools", name, "diff-args"])
+            .unwrap_or_else(|_| vec!["$left".to_string(), "$right".to_string()]);
+        Self { program, args }
+    }
+
+    fn command(&self, trees: &MaterializedTrees, width: u16) -> Command {
+        let left = trees.left.path().to_string_lossy();
+        let right = trees.right.path().to_string_lossy();
+        let width = width.to_string();
+
+        let mut command = Command::new(&self.program);
+        command
+            .args(self.args.iter().map(|arg| {
+                arg.replace("$left", &left)
+                    .replace("$right", &right)
+                    .replace("$width", &width)
+            }))
+            .env("COLUMNS", &width);
+        command
+    }
+}
+
+/// The files that differ between two trees, written to temporary `left` and `right`
+/// directories that are removed again on drop.
+#[derive(Debug)]
+struct MaterializedTrees {
+    left: TempDir,
+    right: TempDir,
+}
+
+impl MaterializedTrees {
+    fn new(
+        from_tree: &MergedTree,
+        to_tree: &MergedTree,
+        matcher: &dyn Matcher,
+        repo: &impl Repo,
+    ) -> Result<Self> {
+        let left = TempDir::with_prefix("diffsoup-left-").change_context(
+            CustomError::ProcessError("could not create dir for the old tree".to_string()),
+        )?;
+        let right = TempDir::with_prefix("diffsoup-right-").change_context(
+            CustomError::ProcessError("could not create dir for the new tree".to_string()),
+        )?;
+
+        let entries = futures::executor::block_on(
+            from_tree.diff_stream(to_tree, matcher).collect::<Vec<_>>(),
+        );
+        for entry in entries {
+            let values = entry.values.change_context(CustomError::RepoError)?;
+            write_value(left.path(), &entry.path, values.before, repo)?;
+            write_value(right.path(), &entry.path, values.after, repo)?;
+        }
+
+        Ok(Self { left, right })
+    }
+}
+
+/// An external tool together with the materialized trees of one diff.
+#[derive(Debug)]
+pub struct ExternalDiff {
+    tool: ExternalTool,
+    trees: MaterializedTrees,
+}
+
+impl ExternalDiff {
+    pub fn new(
+        tool: ExternalTool,
+        trees: &DiffTree,
+        repo: &impl Repo,
+        options: &DiffOptions,
+    ) -> Result<Self> {
+        let (from_tree, to_tree) = trees.get_trees(repo)?;
+        let trees = MaterializedTrees::new(&from_tree, &to_tree, options.matcher().as_ref(), repo)?;
+        Ok(Self { tool, trees })
+    }
+
+    /// Runs the tool and returns its output, which may contain ANSI colors.
+    pub fn capture(&self, width: u16) -> Result<String> {
+        let output = self
+            .tool
+            .command(&self.trees, width)
+            .stdin(Stdio::null())
+            .output()
+            .change_context(self.process_error())?;
+        // Diff tools commonly exit with 1 if the inputs differ
+        if !matches!(output.status.code(), Some(0 | 1)) {
+            return Err(Report::new(self.process_error()).attach(format!(
+                "{}: {}",
+                output.status,
+                String::from_utf8_lossy(&output.stderr).trim()
+            )));
+        }
+        Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+    }
+
+    /// Runs the tool attached to the terminal, for tools with their own interactive interface.
+    pub fn run(&self, width: u16) -> Result<()> {
+        self.tool
+            .command(&self.trees, width)
+            .status()
+            .change_context(self.process_error())?;
+        Ok(())
+    }
+
+    fn process_error(&self) -> CustomError {
+        CustomError::ProcessError(format!("failed to run diff tool `{}`", self.tool.program))
+    }
+}
+
+fn write_value(
+    root: &Path,
+    path: &RepoPath,
+    value: MergedTreeValue,
+    repo: &impl Repo,
+) -> Result<()> {
+    let contents = match value.into_resolved() {
+        Ok(Some(TreeValue::File { id, .. })) => read_file(path, &id, repo)?,
+        Ok(Some(TreeValue::Symlink(id))) => {
+            futures::executor::block_on(repo.store().read_symlink(path, &id))
+                .change_context(CustomError::RepoError)?
+                .into_bytes()
+        }
+        // Absent on this side, or a tree or submodule which have no content to compare
+        Ok(_) => return Ok(()),
+        Err(conflict) => conflict_contents(path, conflict, repo)?,
+    };
+
+    let file_path = path
+        .to_fs_path(root)
+        .change_context(CustomError::RepoError)?;
+    if let Some(parent) = file_path.parent() {
+        fs::create_dir_all(parent).change_context(CustomError::ProcessError(
+            "could not create dir for materialized file".to_string(),
+        ))?;
+    }
+    fs::write(&file_path, contents).change_context(CustomError::ProcessError(
+        "could not write materialized file".to_string(),
+    ))
+}
+
+pub(crate) fn read_file(
+    path: &RepoPath,
+    id: &jj_lib::backend::FileId,
+    repo: &impl Repo,
+) -> Result<Vec<u8>> {
+    let reader = futures::executor::block_on(repo.store().read_file(path, id))
+        .change_context(CustomError::RepoError)?;
+    let mut contents = Vec::new();
+    futures::executor::block_on(copy_async_to_sync(reader, &mut contents))
+        .change_context(CustomError::RepoError)?;
+    Ok(contents)
+}
+
+/// Writes all sides of a conflict with markers, since the tool can't know about jj's conflicts.
+fn conflict_contents(
+    path: &RepoPath,
+    conflict: MergedTreeValue,
+    repo: &impl Repo,
+) -> Result<Vec<u8>> {
+    let side_contents = |value: &Option<TreeValue>| match value {
+        Some(TreeValue::File { id, .. }) => read_file(path, id, repo),
+        _ => Ok(Vec::new()),
+    };
+    let push_section = |contents: &mut Vec<u8>, marker: String, section: Vec<u8>| {
+        contents.extend_from_slice(marker.as_bytes());
+        contents.push(b'\n');
+        if !section.is_empty() && !section.ends_with(b"\n") {
+            contents.extend_from_slice(&section);
+            contents.push(b'\n');
+        } else {
+            contents.extend_from_slice(&section);
+        }
+    };
+
+    let mut contents = Vec::new();
+    let mut removes = conflict.removes();
+    for (index, add) in conflict.adds().enumerate() {
+        let marker = if index == 0 { "<<<<<<<" } else { "=======" };
+        push_section(
+            &mut contents,
+            format!("{marker} side #{}", index + 1),
+            side_contents(add)?,
+        );
+        if let Some(remove) = removes.next() {
+            push_section(
+                &mut contents,
+                format!("||||||| base #{}", index + 1),
+                side_contents(remove)?,
+            );
+        }
+    }
+    contents.extend_from_slice(b">>>>>>> conflict ends\n");
+    Ok(contents)
+}
@@ -0,0 +1,193 @@
+use crate::diff::{CommitDiff, CommitMeta, RenderedInterdiff, short_id};
+
+const STYLE: &str = r#"
+body { font-family: sans-serif; margin: 2em; color: #222; }
+h1 { font-size: 1.4em; }
+h2 { font-size: 1.2em; }
+select { font-size: 1em; margin-bottom: 1em; }
+details, .commit { border: 1px solid #ccc; border-radius: 4px; margin: 0.4em 0; }
+summary, .commit { padding: 0.4em 0.6em; cursor: pointer; }
+.commit { cursor: default; color: #888; }
+.sha { font-family: monospace; }
+.status { display: inline-block; width: 6em; font-weight: bold; }
+.added { color: #1a7f37; }
+.removed { color: #cf222e; }
+.changed { color: #9a6700; }
+.rebased, .unchanged { color: #888; }
+.stats, .note { color: #666; }
+.conflicts { color: #8250df; }
+pre { margin: 0; padding: 0.6em; overflow-x: auto; background: #f6f8fa; font-size: 0.9em; }
+h3 { font-size: 1em; margin: 0.6em; }
+.line-add { background: #e6ffec; color: #1a7f37; }
+.line-del { background: #ffebe9; color: #cf222e; }
+.line-hunk { color: #0969da; }
+.line-file { color: #9a6700; font-weight: bold; }
+"#;
+
+const SCRIPT: &str = r#"
+const select = document.getElementById("pair");
+function showPair() {
+  for (const section of document.querySelectorAll("section.pair")) {
+    section.hidden = section.id !== select.value;
+  }
+}
+select.addEventListener("change", showPair);
+showPair();
+"#;
+
+/// The comparison of one pair of patchsets in a report.
+#[derive(Debug, Clone)]
+pub struct ReportSection {
+    /// Index of the older patchset
+    pub from: usize,
+    /// Index of the newer patchset
+    pub to: usize,
+    /// All matched commits, with the rendered interdiff of the changed ones
+    pub commits: Vec<(CommitDiff, Option<RenderedInterdiff>)>,
+}
+
+/// Renders a self-contained HTML page with a selector for the compared patchset pairs, showing
+/// the last section initially.
+pub fn render_report(title: &str, patchsets: &[String], sections: &[ReportSection]) -> String {
+    let name = |index: usize| {
+        let name = patchsets.get(index).map(String::as_str).unwrap_or_default();
+        format!("{} ({})", index + 1, short_id(name))
+    };
+    let id = |section: &ReportSection| format!("ps-{}-{}", section.from + 1, section.to + 1);
+
+    let mut html = String::new();
+    html.push_str("<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n");
+    html.push_str(&format!("<title>{}</title>\n", escape(title)));
+    html.push_str(&format!("<style>{STYLE}</style>\n</head>\n<body>\n"));
+    html.push_str(&format!("<h1>{}</h1>\n", escape(title)));
+
+    html.push_str("<label>Patchsets <select id=\"pair\">\n");
+    for (index, section) in sections.iter().enumerate() {
+        let selected = if index + 1 == sections.len() {
+            " selected"
+        } else {
+            ""
+        };
+        html.push_str(&format!(
+            "<option value=\"{}\"{selected}>{} → {}</option>\n",
+            id(section),
+            escape(&name(section.from)),
+            escape(&name(section.to))
+        ));
+    }
+    html.push_str("</select></label>\n");
+
+    for section in sections {
+        html.push_str(&format!(
+            "<section class=\"pair\" id=\"{}\">\n",
+            id(section)
+        ));
+        html.push_str(&format!(
+            "<h2>Patchset {} → {}</h2>\n",
+            escape(&name(section.from)),
+            escape(&name(section.to))
+        ));
+        if section.commits.is_empty() {
+            html.push_str("<p class=\"note\">No commits</p>\n");
+        }
+        for (commit, interdiff) in &section.commits {
+            render_commit(&mut html, commit, interdiff.as_ref());
+        }
+        html.push_str("</section>\n");
+    }
+
+    html.push_str(&format!("<script>{SCRIPT}</script>\n</body>\n</html>\n"));
+    html
+}
+
+fn render_commit(html: &mut String, commit: &CommitDiff, interdiff: Option<&RenderedInterdiff>) {
+    let status = commit.status();
+    let meta = commit.to.as_ref().or(commit.from.as_ref());
+    let sha = meta.map(CommitMeta::short_sha).unwrap_or_default();
+    let subject = meta
+        .and_then(|meta| meta.message.lines().next())
+        .unwrap_or("<no message>");
+
+    let mut line = format!(
+        "<span class=\"status {status}\">{status}</span> <span class=\"sha\">{sha}</span> \
+         {}",
+        escape(subject)
+    );
+    if commit.stats.changed_files > 0 {
+        line.push_str(&format!(
+            " <span class=\"stats\">[±{} files, <span class=\"added\">+{}</span>, \
+             <span class=\"removed\">-{}</span>]</span>",
+            commit.stats.changed_files, commit.stats.additions, commit.stats.removals
+        ));
+    }
+    if !commit.conflicts.is_empty() {
+        line.push_str(&format!(
+            " <span class=\"conflicts\">⚠ {} conflicted</span>",
+            commit.conflicts.len()
+        ));
+    }
+
+    let Some(interdiff) = interdiff else {
+        html.push_str(&format!("<div class=\"commit\">{line}</div>\n"));
+        return;
+    };
+    html.push_str(&format!("<details>\n<summary>{line}</summary>\n"));
+    for (title, section) in interdiff.sections() {
+        html.push_str(&format!("<h3>{title}</h3>\n"));
+        render_diff(html, &section);
+    }
+    if !interdiff.conflicts.is_empty() {
+        html.push_str(&format!(
+            "<p class=\"conflicts note\">Approximate interdiff, conflicted: {}</p>\n",
+            escape(&interdiff.conflicts.join(", "))
+        ));
+    }
+    if !interdiff.diff.is_empty() {
+        html.push_str("<h3>Diff</h3>\n");
+        render_diff(html, &interdiff.diff);
+    }
+    html.push_str("</details>\n");
+}
+
+fn render_diff(html: &mut String, diff: &str) {
+    html.push_str("<pre>");
+    for line in diff.lines() {
+        let class = if line.starts_with("diff ") {
+            Some("line-file")
+        } else if line.starts_with("@@") {
+            Some("line-hunk")
+        } else if line.starts_with('+') && !line.starts_with("+++") {
+            Some("line-add")
+        } else if line.starts_with('-') && !line.starts_with("---") {
+            Some("line-del")
+        } else {
+            None
+        };
+        match class {
+            Some(class) => html.push_str(&format!(
+                "<span class=\"{class}\">{}</span>\n",
+                escape(line)
+            )),
+            None => {
+                html.push_str(&escape(line));
+                html.push('\n');
+            }
+        }
+    }
+    html.push_str("</pre>\n");
+}
+
+/// Escapes text for use in HTML elements and quoted attributes.
+pub fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
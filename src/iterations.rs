@@ -0,0 +1,43 @@
+use std::sync::Arc;
+
+use diffsoup::{
+    diff::count_branch_commits,
+    pr::{PrFetcher, fetch_all_history},
+};
+use jj_lib::{repo::ReadonlyRepo, workspace::Workspace};
+
+use crate::{PrArgs, fetch_missing};
+
+#[derive(clap::Args, Debug)]
+pub struct IterationsArgs {
+    #[command(flatten)]
+    pub pr: PrArgs,
+}
+
+/// Prints one tab separated line per patchset: number, sha, push time, pusher and the number of
+/// commits on top of trunk. Unknown values are printed as `-`.
+pub fn run(
+    workspace: Workspace,
+    repo: Arc<ReadonlyRepo>,
+    pr_fetcher: Box<dyn PrFetcher>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let history = fetch_all_history(pr_fetcher.as_ref())?;
+    let repo = fetch_missing(history.iter().map(|iteration| &iteration.name), repo)?;
+
+    for (index, iteration) in history.iter().enumerate() {
+        let commits = count_branch_commits(iteration.name.as_str(), &workspace, repo.as_ref())?;
+        let pushed_at = iteration
+            .pushed_at
+            .map(|date| date.format("%Y-%m-%d %H:%M:%S %:z").to_string())
+            .unwrap_or_else(|| "-".to_string());
+        println!(
+            "{}\t{}\t{}\t{}\t{}",
+            index + 1,
+            iteration.name.as_str(),
+            pushed_at,
+            iteration.pusher.as_deref().unwrap_or("-"),
+            commits
+        );
+    }
+    Ok(())
+}
@@ -0,0 +1,65 @@
+use std::{cell::Cell, rc::Rc};
+
+use clap::ColorChoice;
+use diffsoup::repo::RepoHandle;
+use jj_cli::{
+    cli_util::{CliRunner, CommandHelper},
+    command_error::{CommandError, user_error},
+    ui::Ui,
+};
+
+use crate::{Args, show::OutputArgs};
+
+#[derive(clap::Subcommand, Debug)]
+enum JjCommand {
+    /// Compare the patchsets of a pull request, see `jj diffsoup --help`
+    Diffsoup(Args),
+}
+
+/// Runs jj with `diffsoup` as an additional command and returns the exit code, which is the one
+/// of the diffsoup command if jj itself succeeded.
+pub fn run() -> u8 {
+    // Set by the command, so that jj can finish before exiting, e.g. with `check`'s verdict
+    let exit_code = Rc::new(Cell::new(0));
+    let command_exit_code = exit_code.clone();
+    let jj_exit_code = CliRunner::init()
+        .add_extra_config(diffsoup::config::default_layer())
+        .add_subcommand(
+            move |ui: &mut Ui, command_helper: &CommandHelper, command: JjCommand| {
+                command_exit_code.set(run_command(ui, command_helper, command)?);
+                Ok(())
+            },
+        )
+        .run();
+    match jj_exit_code {
+        0 => exit_code.get(),
+        code => code,
+    }
+}
+
+fn run_command(
+    ui: &mut Ui,
+    command_helper: &CommandHelper,
+    command: JjCommand,
+) -> Result<u8, CommandError> {
+    let JjCommand::Diffsoup(args) = command;
+    // Loaded by jj, so that `--repository`, `--at-op` and `--config` apply. The working copy
+    // isn't snapshotted since only the commits of the pull request are read.
+    let workspace = command_helper.load_workspace()?;
+    let operation = command_helper.resolve_operation(ui, workspace.repo_loader())?;
+    let repo = workspace.repo_loader().load_at(&operation)?;
+    let output_args = OutputArgs {
+        color: if ui.color() {
+            ColorChoice::Always
+        } else {
+            ColorChoice::Never
+        },
+        no_pager: command_helper
+            .global_args()
+            .early_args
+            .no_pager
+            .unwrap_or_default(),
+    };
+    crate::run(args, &output_args, Some(RepoHandle::new(workspace, repo)))
+        .map_err(|e| user_error(e.to_string()))
+}
@@ -0,0 +1,170 @@
+use serde::Serialize;
+
+use crate::{
+    collapse::{CollapsedFile, FileSummary},
+    diff::{CommitDiff, CommitMeta, CommitStatus, DiffStats, MetadataChange, RenderedInterdiff},
+    patch,
+};
+
+/// Version of the JSON schema, incremented on every incompatible change.
+///
+/// Version 2 lists collapsed files in `files`, without hunks and with a `collapsed` summary.
+pub const SCHEMA_VERSION: u32 = 2;
+
+/// The comparison of two patchsets of a pull request.
+#[derive(Debug, Clone, Serialize)]
+pub struct Comparison {
+    pub version: u32,
+    /// Names or shas of all patchsets, oldest first
+    pub patchsets: Vec<String>,
+    pub from: Patchset,
+    pub to: Patchset,
+    pub commits: Vec<Commit>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Patchset {
+    /// Position in `patchsets`, counting from 1
+    pub number: usize,
+    pub name: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Commit {
+    pub status: CommitStatus,
+    pub from: Option<CommitMeta>,
+    pub to: Option<CommitMeta>,
+    pub stats: DiffStats,
+    /// Paths for which the old commit didn't rebase cleanly onto the new base
+    pub conflicts: Vec<String>,
+    pub metadata_changes: Vec<MetadataChange>,
+    /// Hunks of the interdiff per file, only present if requested
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub files: Option<Vec<File>>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct File {
+    pub path: String,
+    /// Always empty for collapsed files
+    pub hunks: Vec<Hunk>,
+    /// Present if the file is binary or too large and was only summarized
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub collapsed: Option<Collapsed>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Collapsed {
+    pub binary: bool,
+    /// `None` if the file didn't exist in the old commit
+    pub before: Option<Summary>,
+    /// `None` if the file was removed
+    pub after: Option<Summary>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Summary {
+    pub size: usize,
+    /// Only counted for files below the size threshold
+    pub lines: Option<usize>,
+    /// Hex id of the file content
+    pub hash: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub image: Option<Image>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Image {
+    pub format: &'static str,
+    pub width: u32,
+    pub height: u32,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Hunk {
+    /// The `@@ -a,b +c,d @@` line
+    pub header: String,
+    /// Lines including their ` `, `-` or `+` prefix
+    pub lines: Vec<String>,
+}
+
+impl Comparison {
+    /// `from` and `to` are indices into `patchsets`.
+    pub fn new(patchsets: Vec<String>, from: usize, to: usize, commits: Vec<Commit>) -> Self {
+        let patchset = |index: usize| Patchset {
+            number: index + 1,
+            name: patchsets.get(index).cloned().unwrap_or_default(),
+        };
+        Self {
+            version: SCHEMA_VERSION,
+            from: patchset(from),
+            to: patchset(to),
+            patchsets,
+            commits,
+        }
+    }
+}
+
+impl Commit {
+    /// `interdiff` is the rendered git diff of the commit, to include its hunks.
+    pub fn new(commit: &CommitDiff, interdiff: Option<&RenderedInterdiff>) -> Self {
+        let metadata_changes = match (&commit.from, &commit.to) {
+            (Some(from), Some(to)) => from.metadata_changes(to),
+            _ => Vec::new(),
+        };
+        Self {
+            status: commit.status(),
+            from: commit.from.clone(),
+            to: commit.to.clone(),
+            stats: commit.stats.clone(),
+            conflicts: commit.conflicts.clone(),
+            metadata_changes,
+            files: interdiff.map(files),
+        }
+    }
+}
+
+fn files(interdiff: &RenderedInterdiff) -> Vec<File> {
+    let mut files: Vec<File> = patch::parse(&interdiff.diff)
+        .into_iter()
+        .map(|file| File {
+            path: file.path.to_string(),
+            hunks: file
+                .hunks
+                .iter()
+                .filter_map(|hunk| {
+                    let (header, lines) = hunk.lines.split_first()?;
+                    Some(Hunk {
+                        header: header.to_string(),
+                        lines: lines.iter().map(|line| line.to_string()).collect(),
+                    })
+                })
+                .collect(),
+            collapsed: None,
+        })
+        .collect();
+    files.extend(interdiff.collapsed.iter().map(collapsed_file));
+    files
+}
+
+fn collapsed_file(file: &CollapsedFile) -> File {
+    let summary = |summary: &FileSummary| Summary {
+        size: summary.size,
+        lines: summary.lines,
+        hash: summary.hash.clone(),
+        image: summary.image.map(|(format, width, height)| Image {
+            format,
+            width,
+            height,
+        }),
+    };
+    File {
+        path: file.path.as_internal_file_string().to_string(),
+        hunks: Vec::new(),
+        collapsed: Some(Collapsed {
+            binary: file.binary,
+            before: file.before.as_ref().map(summary),
+            after: file.after.as_ref().map(summary),
+        }),
+    }
+}
@@ -1,5 +1,15 @@
+pub mod collapse;
+pub mod config;
+pub mod copies;
 pub mod diff;
 pub mod error;
+pub mod external;
+pub mod html;
+pub mod json;
+pub mod markdown;
+pub mod mbox;
+pub mod patch;
 pub mod pr;
+pub mod range_diff;
 pub mod repo;
 pub mod trees;
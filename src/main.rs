@@ -1,37 +1,362 @@
+mod ansi;
+mod check;
+mod completions;
+mod config_list;
+mod export;
+mod iterations;
+mod jj;
+mod report;
+mod show;
 mod tui;
 
-use clap::Parser;
-use diffsoup::{pr::get_pr_fetcher, repo::open};
-use std::path::PathBuf;
+use clap::{CommandFactory, Parser, Subcommand};
+use clap_complete::{CompleteEnv, engine::ArgValueCompleter};
+use diffsoup::{
+    collapse::CollapseThresholds,
+    config::Config,
+    copies::DEFAULT_RENAME_SIMILARITY,
+    diff::{DiffOptions, RenderFormat, parse_filter},
+    external::ExternalTool,
+    pr::{PrFetcher, find_pr_url, get_pr_fetcher},
+    repo::{RepoHandle, current_bookmark, ensure_commits_exist, fetch_commits, open, remote_urls},
+};
+use jj_lib::{
+    backend::CommitId, diff_presentation::LineCompareMode, ref_name::RefNameBuf,
+    repo::ReadonlyRepo, workspace::Workspace,
+};
+use std::{
+    path::{Path, PathBuf},
+    process::ExitCode,
+    sync::Arc,
+};
 
+// diffsoup on its own, which has to provide the output flags that jj has as global flags
 #[derive(Parser, Debug)]
 #[command(name = "diffsoup")]
 #[command(about = "Compare two branches and show interdiff", long_about = None)]
+struct Cli {
+    #[command(flatten)]
+    args: Args,
+
+    #[command(flatten)]
+    output: show::OutputArgs,
+}
+
+#[derive(clap::Args, Debug)]
+#[command(args_conflicts_with_subcommands = true)]
 struct Args {
-    #[arg(long, value_name = "FROM")]
+    #[command(subcommand)]
+    command: Option<Command>,
+
+    #[command(flatten)]
+    diff: DiffArgs,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Print the interdiffs between two patchsets instead of opening the TUI
+    Show(show::ShowArgs),
+    /// List the patchsets of a pull request, numbered as for `--from-ps` and `--to-ps`
+    Iterations(iterations::IterationsArgs),
+    /// Write the commits or interdiffs between two patchsets as mbox patch files
+    Export(export::ExportArgs),
+    /// Write a self-contained HTML report comparing all patchsets
+    Report(report::ReportArgs),
+    /// Exit with 0 if only descriptions changed or commits were rebased, 1 if code or metadata
+    /// changed and 2 on errors
+    Check(check::CheckArgs),
+    /// Inspect the `[diffsoup]` settings in the jj config
+    Config(config_list::ConfigArgs),
+    /// Print the script enabling shell completions, e.g. `source <(diffsoup completions bash)`
+    Completions(completions::CompletionsArgs),
+    /// Print the man page
+    Man,
+}
+
+// Selects the repo and the pull request or branches to compare
+#[derive(clap::Args, Debug)]
+struct PrArgs {
+    #[arg(long, value_name = "FROM", add = ArgValueCompleter::new(completions::complete_bookmark))]
     from: Option<String>,
 
-    #[arg(long, value_name = "TO")]
+    #[arg(long, value_name = "TO", add = ArgValueCompleter::new(completions::complete_bookmark))]
     to: Option<String>,
 
-    #[arg(value_name = "PULL REQUEST URL")]
+    #[arg(
+        value_name = "PULL REQUEST URL",
+        add = ArgValueCompleter::new(completions::complete_pr_url)
+    )]
     pr_url: Option<String>,
 
     #[arg(short, long, default_value = ".")]
     repo: PathBuf,
 }
 
-fn main() -> Result<(), Box<dyn std::error::Error>> {
-    let args = Args::parse();
+// Shared by the TUI and the subcommands that render diffs
+#[derive(clap::Args, Debug)]
+struct DiffArgs {
+    #[command(flatten)]
+    pr: PrArgs,
+
+    /// Minimum similarity in percent for a file to be detected as renamed or copied
+    #[arg(long, value_name = "PERCENT", value_parser = clap::value_parser!(u8).range(0..=100))]
+    rename_similarity: Option<u8>,
+
+    /// Disable rename and copy detection
+    #[arg(long, conflicts_with = "rename_similarity")]
+    no_renames: bool,
+
+    /// Only show changes to files matching this fileset expression, e.g. `src/ ~ glob:"**/*.lock"`,
+    /// defaults to `diffsoup.filter`
+    #[arg(long, value_name = "FILESET")]
+    filter: Option<String>,
+
+    /// Ignore whitespace when comparing lines
+    #[arg(short = 'w', long)]
+    ignore_all_space: bool,
+
+    /// Ignore changes in amount of whitespace when comparing lines
+    #[arg(short = 'b', long, conflicts_with = "ignore_all_space")]
+    ignore_space_change: bool,
+
+    /// Ignore changes that only add or remove blank lines
+    #[arg(long)]
+    ignore_blank_lines: bool,
+
+    /// Diff format to start with: `git`, `color-words`, `range-diff` or `external`, defaults to
+    /// `diffsoup.diff-format`
+    #[arg(long, value_name = "FORMAT")]
+    diff_format: Option<RenderFormat>,
+
+    /// External diff tool from the `merge-tools` config, defaults to jj's `ui.diff-formatter`
+    #[arg(long, value_name = "NAME")]
+    tool: Option<String>,
+
+    /// Summarize files larger than this many KiB instead of diffing them
+    #[arg(long, value_name = "KIB")]
+    collapse_size: Option<usize>,
+
+    /// Summarize files with more than this many lines instead of diffing them
+    #[arg(long, value_name = "LINES")]
+    collapse_lines: Option<usize>,
+
+    /// Leave out merge commits that bring trunk into the branch
+    #[arg(long)]
+    hide_trunk_merges: bool,
+
+    /// Always diff binary and large files
+    #[arg(long, conflicts_with_all = ["collapse_size", "collapse_lines"])]
+    no_collapse: bool,
+}
+
+/// The opened repo and everything needed to diff the patchsets of one PR.
+struct Session {
+    workspace: Workspace,
+    repo: Arc<ReadonlyRepo>,
+    pr: Box<dyn PrFetcher>,
+    options: DiffOptions,
+}
+
+fn main() -> Result<ExitCode, Box<dyn std::error::Error>> {
+    // Installed as `jj`, this is jj with an additional `jj diffsoup` command
+    let program = std::env::args_os().next().map(PathBuf::from);
+    if program.is_some_and(|program| program.file_stem().is_some_and(|stem| stem == "jj")) {
+        return Ok(ExitCode::from(jj::run()));
+    }
+
+    CompleteEnv::with_factory(Cli::command).complete();
+    let cli = Cli::parse();
+    run(cli.args, &cli.output, None).map(ExitCode::from)
+}
+
+/// Runs diffsoup on the repo jj already loaded, or the one at `--repo` otherwise, and returns
+/// the exit code.
+fn run(
+    args: Args,
+    output_args: &show::OutputArgs,
+    loaded: Option<RepoHandle>,
+) -> Result<u8, Box<dyn std::error::Error>> {
+    match args.command {
+        Some(Command::Show(show_args)) => {
+            let session = open_session(&show_args.diff, loaded)?;
+            show::run(
+                session.workspace,
+                session.repo,
+                session.pr,
+                session.options,
+                &show_args,
+                output_args,
+            )?;
+        }
+        Some(Command::Export(export_args)) => {
+            let session = open_session(&export_args.diff, loaded)?;
+            export::run(
+                session.workspace,
+                session.repo,
+                session.pr,
+                session.options,
+                &export_args,
+            )?;
+        }
+        Some(Command::Check(check_args)) => {
+            let verdict = open_session(&check_args.diff, loaded).and_then(|session| {
+                check::run(
+                    session.workspace,
+                    session.repo,
+                    session.pr,
+                    session.options,
+                    &check_args,
+                )
+            });
+            // Errors get their own exit code, since 1 already means that the code changed
+            return match verdict {
+                Ok(verdict) => Ok(verdict.exit_code()),
+                Err(e) => {
+                    eprintln!("Error: {e:?}");
+                    Ok(2)
+                }
+            };
+        }
+        Some(Command::Report(report_args)) => {
+            let session = open_session(&report_args.diff, loaded)?;
+            report::run(
+                session.workspace,
+                session.repo,
+                session.pr,
+                session.options,
+                &report_args,
+            )?;
+        }
+        Some(Command::Config(config_args)) => config_list::run(&config_args, loaded)?,
+        Some(Command::Completions(completions_args)) => completions::run(&completions_args)?,
+        Some(Command::Man) => completions::man()?,
+        Some(Command::Iterations(iterations_args)) => {
+            let (workspace, repo, pr, _) = open_pr(&iterations_args.pr, loaded)?;
+            iterations::run(workspace, repo, pr)?;
+        }
+        None => {
+            let session = open_session(&args.diff, loaded)?;
+            let ui_settings = tui::UiSettings::from_settings(session.workspace.settings())?;
+            tui::run(
+                session.workspace,
+                session.repo,
+                session.pr,
+                session.options,
+                ui_settings,
+            )?;
+        }
+    }
+
+    Ok(0)
+}
+
+type OpenedPr = (Workspace, Arc<ReadonlyRepo>, Box<dyn PrFetcher>, Config);
+
+fn open_pr(
+    args: &PrArgs,
+    loaded: Option<RepoHandle>,
+) -> Result<OpenedPr, Box<dyn std::error::Error>> {
+    let handle = open_repo(&args.repo, loaded)?;
+    let config = Config::from_settings(handle.workspace.settings())?;
+    let pr_url = match (&args.pr_url, &args.from, &args.to) {
+        (None, None, None) => default_pr_url(&handle, &config)?,
+        _ => args.pr_url.clone(),
+    };
+    let pr = get_pr_fetcher(pr_url, args.from.clone(), args.to.clone(), &config)?
+        .ok_or("either a PR URL or --from and --to need to be provided")?;
+    Ok((handle.workspace, handle.repo, pr, config))
+}
+
+fn open_repo(
+    path: &Path,
+    loaded: Option<RepoHandle>,
+) -> Result<RepoHandle, Box<dyn std::error::Error>> {
+    match loaded {
+        Some(handle) => Ok(handle),
+        None => Ok(open(path)?),
+    }
+}
+
+/// Finds the open pull request of the current bookmark on the `upstream` or `origin` remote.
+fn default_pr_url(
+    handle: &RepoHandle,
+    config: &Config,
+) -> Result<Option<String>, Box<dyn std::error::Error>> {
+    let Some(bookmark) = current_bookmark(&handle.workspace, handle.repo.as_ref())? else {
+        return Ok(None);
+    };
+    for remote_url in remote_urls(handle.repo.as_ref())? {
+        if let Some(url) = find_pr_url(&remote_url, &bookmark, config)? {
+            eprintln!("Using pull request {url} of bookmark {bookmark}");
+            return Ok(Some(url));
+        }
+    }
+    Ok(None)
+}
+
+/// Fetches the given patchsets from the remote if they don't exist locally.
+fn fetch_missing<'a>(
+    names: impl Iterator<Item = &'a RefNameBuf>,
+    repo: Arc<ReadonlyRepo>,
+) -> Result<Arc<ReadonlyRepo>, Box<dyn std::error::Error>> {
+    // Branch names given with --from and --to are resolved by jj, only shas can be fetched
+    let shas = names.filter(|name| CommitId::try_from_hex(name.as_str()).is_some());
+    let missing = ensure_commits_exist(shas, repo.as_ref())?;
+    if missing.is_empty() {
+        return Ok(repo);
+    }
+    eprintln!("Missing {} commits, fetching from remote...", missing.len());
+    Ok(fetch_commits(missing.into_iter(), repo)?)
+}
 
-    let handle = open(&args.repo)?;
-    let workspace = handle.workspace;
-    let repo = handle.repo;
+fn open_session(
+    args: &DiffArgs,
+    loaded: Option<RepoHandle>,
+) -> Result<Session, Box<dyn std::error::Error>> {
+    let (workspace, repo, pr, config) = open_pr(&args.pr, loaded)?;
 
-    let pr = get_pr_fetcher(args.pr_url, args.from, args.to)?
-        .expect("either a PR URL or --from  and --to need to be provided");
+    let filter = args
+        .filter
+        .as_deref()
+        .or(config.filter.as_deref())
+        .map(|expr| parse_filter(expr, &workspace))
+        .transpose()?;
+    let external_tool = ExternalTool::from_settings(workspace.settings(), args.tool.as_deref())?;
 
-    tui::run(workspace, repo, pr)?;
+    let options = DiffOptions {
+        rename_similarity: (!args.no_renames).then(|| {
+            args.rename_similarity
+                .map(|percent| f32::from(percent) / 100.0)
+                .unwrap_or(DEFAULT_RENAME_SIMILARITY)
+        }),
+        filter,
+        whitespace: if args.ignore_all_space {
+            Some(LineCompareMode::IgnoreAllSpace)
+        } else if args.ignore_space_change {
+            Some(LineCompareMode::IgnoreSpaceChange)
+        } else {
+            None
+        },
+        ignore_blank_lines: args.ignore_blank_lines,
+        format: args.diff_format.unwrap_or(config.diff_format),
+        external_tool,
+        collapse: (!args.no_collapse).then(|| {
+            let defaults = CollapseThresholds::default();
+            CollapseThresholds {
+                max_bytes: args
+                    .collapse_size
+                    .map(|kib| kib * 1024)
+                    .unwrap_or(defaults.max_bytes),
+                max_lines: args.collapse_lines.unwrap_or(defaults.max_lines),
+            }
+        }),
+        hide_trunk_merges: args.hide_trunk_merges,
+    };
 
-    Ok(())
+    Ok(Session {
+        workspace,
+        repo,
+        pr,
+        options,
+    })
 }
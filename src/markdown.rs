@@ -0,0 +1,105 @@
+use crate::{
+    diff::{CommitDiff, CommitMeta, RenderedInterdiff},
+    html,
+};
+
+/// Renders a summary of the changes between two patchsets for pasting into a PR comment: a table
+/// of the changed commits followed by their interdiffs in collapsed `<details>` blocks.
+pub fn render_summary(
+    from: &str,
+    to: &str,
+    commits: &[(CommitDiff, Option<RenderedInterdiff>)],
+) -> String {
+    let changed: Vec<&(CommitDiff, Option<RenderedInterdiff>)> = commits
+        .iter()
+        .filter(|(commit, _)| commit.has_changes())
+        .collect();
+
+    let mut markdown = format!("### Changes from patchset {from} to {to}\n\n");
+    if changed.is_empty() {
+        markdown.push_str("No changes.\n");
+        return markdown;
+    }
+
+    markdown.push_str("| Status | Commit | Subject | Files | + | - |\n");
+    markdown.push_str("|---|---|---|--:|--:|--:|\n");
+    for (commit, _) in &changed {
+        let sha_info = match (&commit.from, &commit.to) {
+            (Some(from), Some(to)) => format!("`{}` → `{}`", from.short_sha(), to.short_sha()),
+            (Some(meta), None) | (None, Some(meta)) => format!("`{}`", meta.short_sha()),
+            (None, None) => String::new(),
+        };
+        let mut status = commit.status().to_string();
+        if !commit.conflicts.is_empty() {
+            status.push_str(" ⚠");
+        }
+        markdown.push_str(&format!(
+            "| {status} | {sha_info} | {} | {} | {} | {} |\n",
+            subject(commit).replace('|', "\\|"),
+            commit.stats.changed_files,
+            commit.stats.additions,
+            commit.stats.removals
+        ));
+    }
+    let unchanged = commits.len() - changed.len();
+    if unchanged > 0 {
+        markdown.push_str(&format!("\n{unchanged} unchanged commits not shown.\n"));
+    }
+
+    for (commit, interdiff) in &changed {
+        let Some(interdiff) = interdiff else {
+            continue;
+        };
+        let sha = commit
+            .to
+            .as_ref()
+            .or(commit.from.as_ref())
+            .map(CommitMeta::short_sha)
+            .unwrap_or_default();
+        markdown.push_str(&format!(
+            "\n<details>\n<summary><code>{sha}</code> {}</summary>\n\n",
+            html::escape(subject(commit))
+        ));
+        if !interdiff.conflicts.is_empty() {
+            markdown.push_str(&format!(
+                "Approximate interdiff, conflicted: {}\n\n",
+                interdiff
+                    .conflicts
+                    .iter()
+                    .map(|path| format!("`{path}`"))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ));
+        }
+        let mut sections = interdiff.sections();
+        if !interdiff.diff.is_empty() {
+            sections.push(("Diff", interdiff.diff.clone()));
+        }
+        for (title, section) in sections {
+            markdown.push_str(&format!("**{title}**\n\n{}\n", code_block(&section)));
+        }
+        markdown.push_str("</details>\n");
+    }
+    markdown
+}
+
+fn subject(commit: &CommitDiff) -> &str {
+    commit
+        .to
+        .as_ref()
+        .or(commit.from.as_ref())
+        .and_then(|meta| meta.message.lines().next())
+        .unwrap_or("<no message>")
+}
+
+/// Wraps text in a `diff` code block, with a fence longer than any backtick run inside.
+fn code_block(text: &str) -> String {
+    let longest_run = text
+        .split(|c| c != '`')
+        .map(str::len)
+        .max()
+        .unwrap_or_default();
+    let fence = "`".repeat(longest_run.max(2) + 1);
+    let newline = if text.ends_with('\n') { "" } else { "\n" };
+    format!("{fence}diff\n{text}{newline}{fence}\n")
+}
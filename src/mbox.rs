@@ -0,0 +1,301 @@
+use error_stack::Report;
+
+use crate::{
+    diff::CommitMeta,
+    error::{CustomError, Result},
+};
+
+/// Formats a commit with its diff like `git format-patch --pretty=mboxrd`, as a single mbox
+/// message that can be sent by mail or applied with `git am --patch-format=mboxrd`.
+///
+/// `notes` are placed between the `---` separator and the diff, where `git am` ignores them.
+/// Fails for binary files and for messages that `git am` would cut short, since the patch
+/// couldn't be applied as intended.
+pub fn format_patch(
+    meta: &CommitMeta,
+    subject_prefix: &str,
+    notes: Option<&str>,
+    diff: &str,
+) -> Result<String> {
+    let (subject, body) = split_message(&meta.message);
+    if let Some(path) = binary_file(diff) {
+        return Err(Report::new(CustomError::CommitError(format!(
+            "{} changes the binary file {path}, which can't be exported as a patch",
+            meta.sha
+        ))));
+    }
+    if let Some(line) = body.lines().find(|line| is_patch_break(line)) {
+        return Err(Report::new(CustomError::CommitError(format!(
+            "The message of {} contains the line `{line}`, which `git am` would take for the \
+             start of the diff",
+            meta.sha
+        ))));
+    }
+    let date = chrono::DateTime::parse_from_str(&meta.author_date, "%Y-%m-%d %H:%M:%S %:z")
+        .map(|date| date.to_rfc2822())
+        .unwrap_or_else(|_| meta.author_date.clone());
+
+    let mut text = String::new();
+    if !body.is_empty() {
+        text.push_str(body);
+        text.push('\n');
+    }
+    text.push_str("---\n");
+    if let Some(notes) = notes {
+        text.push_str(notes);
+        if !notes.ends_with('\n') {
+            text.push('\n');
+        }
+        text.push('\n');
+    }
+    text.push_str(diff);
+    if !diff.ends_with('\n') {
+        text.push('\n');
+    }
+    text.push_str(&format!("-- \ndiffsoup {}\n", env!("CARGO_PKG_VERSION")));
+
+    Ok(format_message(
+        &meta.sha,
+        &meta.author,
+        &date,
+        &format!("[{subject_prefix}] {subject}"),
+        &text,
+    ))
+}
+
+/// Formats the cover letter sent before the patches, listing them along with the merge commits
+/// that were left out because they can't be applied as patches.
+pub fn format_cover_letter(
+    sender: &str,
+    subject: &str,
+    introduction: &str,
+    patches: &[&CommitMeta],
+    skipped_merges: &[&CommitMeta],
+) -> String {
+    let list = |metas: &[&CommitMeta]| {
+        metas
+            .iter()
+            .map(|meta| {
+                let (subject, _) = split_message(&meta.message);
+                format!("  {} {subject}\n", meta.short_sha())
+            })
+            .collect::<String>()
+    };
+    let mut text = format!("{introduction}\n\n");
+    text.push_str(&list(patches));
+    if !skipped_merges.is_empty() {
+        text.push_str("\nSkipped merge commits, which can't be exported as patches:\n\n");
+        text.push_str(&list(skipped_merges));
+    }
+    text.push_str(&format!("\n-- \ndiffsoup {}\n", env!("CARGO_PKG_VERSION")));
+
+    // Like git, the cover letter isn't attributed to any commit
+    let null_sha = "0".repeat(40);
+    let date = chrono::Local::now().to_rfc2822();
+    format_message(&null_sha, sender, &date, subject, &text)
+}
+
+/// Assembles the headers and the `From `-escaped text into one mbox message.
+fn format_message(sha: &str, from: &str, date: &str, subject: &str, text: &str) -> String {
+    let mut message = String::new();
+    // The fixed date is the magic marker git uses for patches in mbox format
+    message.push_str(&format!("From {sha} Mon Sep 17 00:00:00 2001\n"));
+    message.push_str(&format!("From: {}\n", encode_address(from)));
+    message.push_str(&format!("Date: {date}\n"));
+    message.push_str(&format!("Subject: {}\n", encode_header(subject)));
+    if !from.is_ascii() || !text.is_ascii() || !subject.is_ascii() {
+        message.push_str("MIME-Version: 1.0\n");
+        message.push_str("Content-Type: text/plain; charset=UTF-8\n");
+        message.push_str("Content-Transfer-Encoding: 8bit\n");
+    }
+    message.push('\n');
+    for line in text.lines() {
+        // mboxrd quoting, which `git am --patch-format=mboxrd` reverts
+        if line.trim_start_matches('>').starts_with("From ") {
+            message.push('>');
+        }
+        message.push_str(line);
+        message.push('\n');
+    }
+    message.push('\n');
+    message
+}
+
+/// The path of the first file that jj could only diff as `Binary files ... differ`.
+fn binary_file(diff: &str) -> Option<&str> {
+    diff.lines().find_map(|line| {
+        let paths = line
+            .strip_prefix("Binary files ")?
+            .strip_suffix(" differ")?;
+        let (old, new) = paths.rsplit_once(" and ")?;
+        let path = if new == "/dev/null" { old } else { new };
+        Some(path.get(2..).unwrap_or(path))
+    })
+}
+
+/// Whether `git am` ends the commit message at this line, like `git mailinfo` does.
+fn is_patch_break(line: &str) -> bool {
+    let separator = line
+        .strip_prefix("---")
+        .is_some_and(|rest| rest.is_empty() || rest.starts_with(char::is_whitespace));
+    separator || line.starts_with("diff -") || line.starts_with("Index: ")
+}
+
+/// The `git format-patch` file name for the `number`th patch, e.g. `0001-fix-the-thing.patch`.
+pub fn patch_file_name(number: usize, meta: &CommitMeta) -> String {
+    let (subject, _) = split_message(&meta.message);
+    let mut slug = String::new();
+    for c in subject.chars() {
+        if c.is_ascii_alphanumeric() || c == '_' || c == '.' {
+            slug.push(c);
+        } else if !slug.is_empty() && !slug.ends_with('-') {
+            slug.push('-');
+        }
+    }
+    let slug: String = slug.chars().take(52).collect();
+    format!("{number:04}-{}.patch", slug.trim_end_matches(['-', '.']))
+}
+
+/// Splits a commit message into the subject, with wrapped lines joined, and the body.
+fn split_message(message: &str) -> (String, &str) {
+    let message = message.trim();
+    let (subject, body) = message.split_once("\n\n").unwrap_or((message, ""));
+    let subject = subject.lines().map(str::trim).collect::<Vec<_>>().join(" ");
+    (subject, body.trim_start_matches('\n'))
+}
+
+/// Encodes the name of a `Name <email>` address if it isn't plain ASCII.
+fn encode_address(address: &str) -> String {
+    match address.rsplit_once(" <") {
+        Some((name, email)) if !name.is_ascii() => format!("{} <{email}", encode_header(name)),
+        _ => address.to_string(),
+    }
+}
+
+/// Encodes a header value as an RFC 2047 encoded word if it isn't plain ASCII.
+fn encode_header(value: &str) -> String {
+    if value.is_ascii() {
+        return value.to_string();
+    }
+    let mut encoded = String::from("=?UTF-8?q?");
+    for byte in value.bytes() {
+        match byte {
+            b' ' => encoded.push('_'),
+            b'a'..=b'z' | b'A'..=b'Z' | b'0'..=b'9' | b'.' | b',' | b'-' | b'/' | b':' => {
+                encoded.push(char::from(byte))
+            }
+            _ => encoded.push_str(&format!("={byte:02X}")),
+        }
+    }
+    encoded.push_str("?=");
+    encoded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn meta(message: &str) -> CommitMeta {
+        CommitMeta {
+            sha: "0123456789abcdef0123456789abcdef01234567".to_string(),
+            change_id: "zyxwvutsrqponmlkzyxwvutsrqponmlk".to_string(),
+            message: message.to_string(),
+            author: "Jane Doe <jane@example.com>".to_string(),
+            author_date: "2025-01-02 03:04:05 +01:00".to_string(),
+            committer: "Jane Doe <jane@example.com>".to_string(),
+            signed: false,
+            parents: 1,
+        }
+    }
+
+    const DIFF: &str = "diff --git a/file b/file\n--- a/file\n+++ b/file\n@@ -1 +1 @@\n-a\n+b\n";
+
+    #[test]
+    fn format_patch_headers_and_body() {
+        let patch = format_patch(&meta("Fix it\n\nBecause.\n"), "PATCH 1/1", None, DIFF).unwrap();
+        assert!(patch.starts_with(
+            "From 0123456789abcdef0123456789abcdef01234567 Mon Sep 17 00:00:00 2001\n\
+             From: Jane Doe <jane@example.com>\n\
+             Date: Thu, 2 Jan 2025 03:04:05 +0100\n\
+             Subject: [PATCH 1/1] Fix it\n\n\
+             Because.\n---\ndiff --git"
+        ));
+        let signature = format!("+b\n-- \ndiffsoup {}\n\n", env!("CARGO_PKG_VERSION"));
+        assert!(patch.ends_with(&signature));
+    }
+
+    #[test]
+    fn format_patch_escapes_from_lines() {
+        let patch = format_patch(
+            &meta("Subject\n\nFrom here on\n>From there\nNot From\n"),
+            "PATCH",
+            None,
+            DIFF,
+        )
+        .unwrap();
+        assert!(patch.contains("\n>From here on\n>>From there\nNot From\n"));
+        assert_eq!(patch.matches("\nFrom ").count(), 0);
+    }
+
+    #[test]
+    fn format_patch_refuses_patch_breaks_in_message() {
+        for line in ["---", "--- ", "diff -u a b", "Index: file"] {
+            let message = format!("Subject\n\nBefore\n{line}\nAfter\n");
+            assert!(format_patch(&meta(&message), "PATCH", None, DIFF).is_err());
+        }
+        let message = "Subject\n\n----\n--x\n";
+        assert!(format_patch(&meta(message), "PATCH", None, DIFF).is_ok());
+    }
+
+    #[test]
+    fn format_patch_refuses_binary_files() {
+        let diff = "diff --git a/logo.png b/logo.png\nindex 1..2 100644\n\
+                    Binary files a/logo.png and b/logo.png differ\n";
+        let error = format_patch(&meta("Subject"), "PATCH", None, diff).unwrap_err();
+        assert!(error.to_string().contains("logo.png"));
+        assert_eq!(
+            binary_file("Binary files a/old.bin and /dev/null differ\n"),
+            Some("old.bin")
+        );
+        assert_eq!(binary_file(DIFF), None);
+    }
+
+    #[test]
+    fn cover_letter_lists_skipped_merges() {
+        let patch = meta("Add the feature\n");
+        let mut merge = meta("Merge trunk\n");
+        merge.parents = 2;
+        let letter = format_cover_letter(
+            "Jane Doe <jane@example.com>",
+            "[PATCH 0/1] Patchset 2 (abc)",
+            "The commits of patchset 2 (abc):",
+            &[&patch],
+            &[&merge],
+        );
+        assert!(letter.starts_with(
+            "From 0000000000000000000000000000000000000000 Mon Sep 17 00:00:00 2001\n"
+        ));
+        assert!(letter.contains("Subject: [PATCH 0/1] Patchset 2 (abc)\n"));
+        assert!(letter.contains("  01234567 Add the feature\n"));
+        assert!(letter.contains("Skipped merge commits"));
+        assert!(letter.contains("  01234567 Merge trunk\n"));
+    }
+
+    #[test]
+    fn encode_non_ascii_headers() {
+        assert_eq!(encode_header("plain"), "plain");
+        assert_eq!(encode_header("Grüße"), "=?UTF-8?q?Gr=C3=BC=C3=9Fe?=");
+        assert_eq!(
+            encode_address("Jürgen <j@example.com>"),
+            "=?UTF-8?q?J=C3=BCrgen?= <j@example.com>"
+        );
+    }
+
+    #[test]
+    fn patch_file_names() {
+        assert_eq!(
+            patch_file_name(1, &meta("Fix: the thing!\n")),
+            "0001-Fix-the-thing.patch"
+        );
+    }
+}
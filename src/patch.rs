@@ -0,0 +1,378 @@
+use jj_lib::diff::{ContentDiff, DiffHunkKind};
+use std::collections::{HashMap, HashSet};
+
+/// A single file section of a rendered git diff.
+#[derive(Debug, Clone)]
+pub struct FilePatch<'a> {
+    pub path: &'a str,
+    /// Index of the `diff --git` line within the rendered diff
+    pub start: usize,
+    pub hunks: Vec<Hunk<'a>>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Hunk<'a> {
+    /// Index of the `@@` line within the rendered diff
+    pub start: usize,
+    pub lines: Vec<&'a str>,
+}
+
+impl<'a> Hunk<'a> {
+    pub fn end(&self) -> usize {
+        self.start + self.lines.len()
+    }
+
+    pub fn changed_lines(&self) -> impl Iterator<Item = &'a str> + '_ {
+        self.lines
+            .iter()
+            .skip(1)
+            .filter(|line| line.starts_with('+') || line.starts_with('-'))
+            .copied()
+    }
+}
+
+pub fn parse(diff: &str) -> Vec<FilePatch<'_>> {
+    let mut files: Vec<FilePatch> = Vec::new();
+    for (index, line) in diff.lines().enumerate() {
+        if let Some(paths) = line.strip_prefix("diff --git ") {
+            let path = paths
+                .rsplit_once(" b/")
+                .map(|(_, path)| path)
+                .unwrap_or(paths);
+            files.push(FilePatch {
+                path,
+                start: index,
+                hunks: Vec::new(),
+            });
+            continue;
+        }
+        let Some(file) = files.last_mut() else {
+            continue;
+        };
+        if line.starts_with("@@") {
+            file.hunks.push(Hunk {
+                start: index,
+                lines: vec![line],
+            });
+        } else if let Some(hunk) = file.hunks.last_mut() {
+            hunk.lines.push(line);
+        }
+    }
+    files
+}
+
+/// Returns the line indices of all hunks in `interdiff` whose changes appear, in the same order
+/// and without other changes in between, in a hunk of the same file in `upstream`, meaning they
+/// were most likely introduced by rebasing onto a newer base rather than by the author.
+pub fn upstream_lines(interdiff: &str, upstream: &str) -> HashSet<usize> {
+    let upstream_hunks: HashMap<&str, Vec<Vec<&str>>> = parse(upstream)
+        .into_iter()
+        .map(|file| {
+            let hunks = file
+                .hunks
+                .iter()
+                .map(|hunk| hunk.changed_lines().collect())
+                .collect();
+            (file.path, hunks)
+        })
+        .collect();
+
+    let mut lines = HashSet::new();
+    for file in parse(interdiff) {
+        let Some(upstream_hunks) = upstream_hunks.get(file.path) else {
+            continue;
+        };
+        for hunk in &file.hunks {
+            let changed: Vec<&str> = hunk.changed_lines().collect();
+            if changed.is_empty() {
+                continue;
+            }
+            let from_upstream = upstream_hunks.iter().any(|upstream_changed| {
+                upstream_changed
+                    .windows(changed.len())
+                    .any(|window| window == changed.as_slice())
+            });
+            if from_upstream {
+                lines.extend(hunk.start..hunk.end());
+            }
+        }
+    }
+    lines
+}
+
+/// Removes all hunks that only add or remove blank lines, along with the file sections that are
+/// left without any hunks.
+pub fn strip_blank_line_hunks(diff: &str) -> String {
+    let files = parse(diff);
+    let line_count = diff.lines().count();
+    let mut skipped = HashSet::new();
+    for (i, file) in files.iter().enumerate() {
+        let blank_hunks: Vec<&Hunk> = file
+            .hunks
+            .iter()
+            .filter(|hunk| hunk.changed_lines().all(|line| line[1..].trim().is_empty()))
+            .collect();
+        if !file.hunks.is_empty() && blank_hunks.len() == file.hunks.len() {
+            let end = files
+                .get(i + 1)
+                .map(|next| next.start)
+                .unwrap_or(line_count);
+            skipped.extend(file.start..end);
+        } else {
+            skipped.extend(blank_hunks.iter().flat_map(|hunk| hunk.start..hunk.end()));
+        }
+    }
+
+    let mut stripped = String::with_capacity(diff.len());
+    for (index, line) in diff.lines().enumerate() {
+        if !skipped.contains(&index) {
+            stripped.push_str(line);
+            stripped.push('\n');
+        }
+    }
+    stripped
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SideBySideRow<'a> {
+    /// File and hunk headers spanning both columns
+    Header(&'a str),
+    /// Lines including their diff prefix, `None` where the other side has no counterpart
+    Lines {
+        left: Option<&'a str>,
+        right: Option<&'a str>,
+    },
+}
+
+/// Splits a git diff into rows of old and new lines, pairing up removals with the additions
+/// that replaced them.
+pub fn side_by_side(diff: &str) -> Vec<SideBySideRow<'_>> {
+    side_by_side_from(diff, false)
+}
+
+/// Like [`side_by_side`], for text consisting of hunk lines only, without any headers.
+pub fn side_by_side_hunk(hunk: &str) -> Vec<SideBySideRow<'_>> {
+    side_by_side_from(hunk, true)
+}
+
+fn side_by_side_from(diff: &str, in_hunk: bool) -> Vec<SideBySideRow<'_>> {
+    fn flush<'a>(
+        rows: &mut Vec<SideBySideRow<'a>>,
+        removed: &mut Vec<&'a str>,
+        added: &mut Vec<&'a str>,
+    ) {
+        let length = removed.len().max(added.len());
+        let mut removed = removed.drain(..);
+        let mut added = added.drain(..);
+        for _ in 0..length {
+            rows.push(SideBySideRow::Lines {
+                left: removed.next(),
+                right: added.next(),
+            });
+        }
+    }
+
+    let mut rows = Vec::new();
+    let mut removed = Vec::new();
+    let mut added = Vec::new();
+    let mut in_hunk = in_hunk;
+    for line in diff.lines() {
+        if line.starts_with("diff --git") {
+            in_hunk = false;
+        } else if line.starts_with("@@") {
+            in_hunk = true;
+        } else if in_hunk && line.starts_with('-') {
+            removed.push(line);
+            continue;
+        } else if in_hunk && line.starts_with('+') {
+            added.push(line);
+            continue;
+        }
+
+        flush(&mut rows, &mut removed, &mut added);
+        if in_hunk && line.starts_with(' ') {
+            rows.push(SideBySideRow::Lines {
+                left: Some(line),
+                right: Some(line),
+            });
+        } else {
+            rows.push(SideBySideRow::Header(line));
+        }
+    }
+    flush(&mut rows, &mut removed, &mut added);
+    rows
+}
+
+/// Diffs two texts line by line, prefixing each line with ` `, `-` or `+`.
+pub fn diff_lines(old: &str, new: &str) -> Vec<String> {
+    // A missing newline at the end would make the last line differ from an otherwise equal one
+    let terminate = |text: &str| {
+        if text.is_empty() || text.ends_with('\n') {
+            text.to_string()
+        } else {
+            format!("{text}\n")
+        }
+    };
+    let (old, new) = (terminate(old), terminate(new));
+
+    let mut lines = Vec::new();
+    for hunk in ContentDiff::by_line([&old, &new]).hunks() {
+        match hunk.kind {
+            DiffHunkKind::Matching => {
+                push_prefixed(&mut lines, ' ', hunk.contents[0]);
+            }
+            DiffHunkKind::Different => {
+                push_prefixed(&mut lines, '-', hunk.contents[0]);
+                push_prefixed(&mut lines, '+', hunk.contents[1]);
+            }
+        }
+    }
+    lines
+}
+
+fn push_prefixed(lines: &mut Vec<String>, prefix: char, content: &[u8]) {
+    lines.extend(
+        String::from_utf8_lossy(content)
+            .lines()
+            .map(|line| format!("{prefix}{line}")),
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DIFF: &str = "\
+diff --git a/src/a.rs b/src/a.rs
+index 1111111..2222222 100644
+--- a/src/a.rs
++++ b/src/a.rs
+@@ -1,3 +1,3 @@
+ fn a() {
+-    old();
++    new();
+ }
+diff --git a/src/b.rs b/src/b.rs
+--- a/src/b.rs
++++ b/src/b.rs
+@@ -1,2 +1,3 @@
+ fn b() {
++    added();
+ }
+@@ -10,2 +11,2 @@
+-x
++y
+";
+
+    #[test]
+    fn parse_files_and_hunks() {
+        let files = parse(DIFF);
+        assert_eq!(files.len(), 2);
+        assert_eq!(files[0].path, "src/a.rs");
+        assert_eq!(files[0].start, 0);
+        assert_eq!(files[0].hunks.len(), 1);
+        assert_eq!(files[0].hunks[0].start, 4);
+        assert_eq!(files[0].hunks[0].end(), 9);
+        assert_eq!(
+            files[0].hunks[0].changed_lines().collect::<Vec<_>>(),
+            ["-    old();", "+    new();"]
+        );
+        assert_eq!(files[1].path, "src/b.rs");
+        assert_eq!(files[1].start, 9);
+        assert_eq!(files[1].hunks.len(), 2);
+        assert_eq!(files[1].hunks[1].lines, ["@@ -10,2 +11,2 @@", "-x", "+y"]);
+    }
+
+    #[test]
+    fn parse_ignores_lines_before_first_file() {
+        let files = parse("some preamble\n+not a change\n");
+        assert!(files.is_empty());
+    }
+
+    #[test]
+    fn strip_blank_line_hunks_keeps_other_changes() {
+        let diff = "\
+diff --git a/a b/a
+@@ -1,2 +1,3 @@
+ x
++
+@@ -5,2 +6,2 @@
+-y
++z
+diff --git a/b b/b
+@@ -1,2 +1,1 @@
+-\t
+ w
+";
+        let expected = "\
+diff --git a/a b/a
+@@ -5,2 +6,2 @@
+-y
++z
+";
+        assert_eq!(strip_blank_line_hunks(diff), expected);
+    }
+
+    #[test]
+    fn strip_blank_line_hunks_without_blank_lines() {
+        assert_eq!(strip_blank_line_hunks(DIFF), DIFF);
+    }
+
+    #[test]
+    fn upstream_lines_match_whole_hunk_in_order() {
+        let upstream = "\
+diff --git a/src/a.rs b/src/a.rs
+@@ -1,3 +1,3 @@
+ fn a() {
+-    old();
++    new();
++    more();
+ }
+";
+        let lines = upstream_lines(DIFF, upstream);
+        // The hunk of `src/a.rs` appears in the upstream hunk, nothing in `src/b.rs` does
+        assert_eq!(lines, (4..9).collect());
+    }
+
+    #[test]
+    fn upstream_lines_require_same_order() {
+        let upstream = "\
+diff --git a/src/a.rs b/src/a.rs
+@@ -1,3 +1,3 @@
++    new();
+-    old();
+";
+        assert!(upstream_lines(DIFF, upstream).is_empty());
+    }
+
+    #[test]
+    fn upstream_lines_ignore_common_lines_from_other_hunks() {
+        let interdiff = "\
+diff --git a/src/a.rs b/src/a.rs
+@@ -1,2 +1,3 @@
+ fn a() {
++}
++x
+";
+        // Both lines occur upstream, but in different hunks
+        let upstream = "\
+diff --git a/src/a.rs b/src/a.rs
+@@ -1,2 +1,2 @@
++}
+@@ -5,2 +5,2 @@
++x
+";
+        assert!(upstream_lines(interdiff, upstream).is_empty());
+    }
+
+    #[test]
+    fn upstream_lines_only_compare_the_same_file() {
+        let upstream = "\
+diff --git a/src/other.rs b/src/other.rs
+@@ -1,3 +1,3 @@
+-    old();
++    new();
+";
+        assert!(upstream_lines(DIFF, upstream).is_empty());
+    }
+}
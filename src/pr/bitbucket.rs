@@ -1,3 +1,4 @@
+use chrono::DateTime;
 use error_stack::ResultExt;
 use jj_lib::ref_name::RefNameBuf;
 use reqwest::header::{AUTHORIZATION, HeaderMap};
@@ -6,7 +7,7 @@ use url::Url;
 
 use crate::{
     error::{CustomError, Result},
-    pr::{OffsetPagination, Page, PageDirection, Pagination, PrFetcher},
+    pr::{Iteration, OffsetPagination, Page, PageDirection, Pagination, PrFetcher},
 };
 
 #[derive(Debug)]
@@ -16,25 +17,12 @@ pub struct BitbucketFetcher {
     project: String,
     repo: String,
     pr_id: String,
+    page_size: usize,
 }
 
 impl BitbucketFetcher {
-    pub fn new(url: &Url, token: Option<String>) -> Result<Self> {
-        let mut headers = HeaderMap::new();
-        if let Some(token) = &token {
-            headers.insert(
-                AUTHORIZATION,
-                format!("Bearer {}", token)
-                    .parse()
-                    .change_context(CustomError::UrlError)?,
-            );
-        }
-        let client = reqwest::blocking::Client::builder()
-            .default_headers(headers)
-            .build()
-            .change_context(CustomError::ProcessError(
-                "error building client".to_string(),
-            ))?;
+    pub fn new(url: &Url, token: Option<String>, page_size: usize) -> Result<Self> {
+        let client = client(token.as_deref())?;
         let host = url.origin().unicode_serialization();
         let segments: Vec<&str> = url.path_segments().ok_or(CustomError::UrlError)?.collect();
 
@@ -53,12 +41,84 @@ impl BitbucketFetcher {
                 project: project.to_string(),
                 repo: repo.to_string(),
                 pr_id: pr_id.to_string(),
+                page_size,
             }),
             _ => Err(CustomError::UrlError.into()),
         }
     }
 }
 
+fn client(token: Option<&str>) -> Result<reqwest::blocking::Client> {
+    let mut headers = HeaderMap::new();
+    if let Some(token) = token {
+        headers.insert(
+            AUTHORIZATION,
+            format!("Bearer {}", token)
+                .parse()
+                .change_context(CustomError::UrlError)?,
+        );
+    }
+    reqwest::blocking::Client::builder()
+        .default_headers(headers)
+        .build()
+        .change_context(CustomError::ProcessError(
+            "error building client".to_string(),
+        ))
+}
+
+/// Returns the URL of the open pull request from `branch` of `project/repo` on `host`, if there
+/// is one.
+pub fn find_pull_request(
+    host: &str,
+    project: &str,
+    repo: &str,
+    branch: &str,
+    token: Option<&str>,
+) -> Result<Option<String>> {
+    let at = format!("refs/heads/{branch}");
+    let res: PullRequests = client(token)?
+        .get(format!(
+            "{host}/rest/api/latest/projects/{project}/repos/{repo}/pull-requests"
+        ))
+        .query(&[
+            ("at", at.as_str()),
+            ("direction", "OUTGOING"),
+            ("state", "OPEN"),
+            ("limit", "1"),
+        ])
+        .send()
+        .change_context(CustomError::RequestError)?
+        .json()
+        .change_context(CustomError::RequestError)?;
+    Ok(res
+        .values
+        .into_iter()
+        .next()
+        .and_then(|pull_request| pull_request.links.self_links.into_iter().next())
+        .map(|link| link.href))
+}
+
+#[derive(Debug, Deserialize)]
+struct PullRequests {
+    values: Vec<PullRequestEntry>,
+}
+
+#[derive(Debug, Deserialize)]
+struct PullRequestEntry {
+    links: Links,
+}
+
+#[derive(Debug, Deserialize)]
+struct Links {
+    #[serde(rename = "self")]
+    self_links: Vec<Link>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Link {
+    href: String,
+}
+
 #[derive(Debug, Deserialize)]
 #[serde(rename_all = "camelCase")]
 struct PrActivity {
@@ -70,20 +130,33 @@ struct PrActivity {
     values: Vec<PrActivityEntry>,
 }
 
-impl From<PrActivity> for Page<RefNameBuf> {
+impl From<PrActivity> for Page<Iteration> {
     fn from(value: PrActivity) -> Self {
         let actions = value.values.iter().filter_map(|v| match v {
             PrActivityEntry::Rescoped(action) => Some(action),
             _ => None,
         });
+        let opened = value.values.iter().find_map(|v| match v {
+            PrActivityEntry::Opened(action) => Some(action),
+            _ => None,
+        });
 
         let mut commits = Vec::new();
 
         for (i, action) in actions.rev().enumerate() {
             if value.is_last_page && i == 0 {
-                commits.push(RefNameBuf::from(&action.previous_from_hash));
+                commits.push(Iteration {
+                    name: RefNameBuf::from(&action.previous_from_hash),
+                    pushed_at: opened
+                        .and_then(|opened| DateTime::from_timestamp_millis(opened.created_date)),
+                    pusher: opened.map(|opened| opened.user.display_name.clone()),
+                });
             }
-            commits.push(RefNameBuf::from(&action.from_hash));
+            commits.push(Iteration {
+                name: RefNameBuf::from(&action.from_hash),
+                pushed_at: DateTime::from_timestamp_millis(action.created_date),
+                pusher: Some(action.user.display_name.clone()),
+            });
         }
 
         Self {
@@ -101,6 +174,7 @@ impl From<PrActivity> for Page<RefNameBuf> {
 #[derive(Debug, Deserialize)]
 #[serde(tag = "action", rename_all = "UPPERCASE")]
 enum PrActivityEntry {
+    Opened(PrOpenAction),
     Rescoped(PrRescopeAction),
     #[serde(other)]
     Unknown,
@@ -109,16 +183,31 @@ enum PrActivityEntry {
 #[derive(Debug, Deserialize)]
 #[serde(rename_all = "camelCase")]
 struct PrRescopeAction {
+    created_date: i64,
+    user: User,
     from_hash: String,
     previous_from_hash: String,
     _to_hash: String,
     _previous_to_hash: String,
 }
 
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct PrOpenAction {
+    created_date: i64,
+    user: User,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct User {
+    display_name: String,
+}
+
 impl PrFetcher for BitbucketFetcher {
-    fn fetch_history(&self, pagination: Option<&Pagination>) -> Result<Page<RefNameBuf>> {
+    fn fetch_history(&self, pagination: Option<&Pagination>) -> Result<Page<Iteration>> {
         let (offset, limit) = match pagination {
-            None => (0, None),
+            None => (0, Some(self.page_size)),
             Some(Pagination::Offset(pagination)) => (pagination.offset, pagination.limit),
             _ => {
                 return Err(CustomError::ProcessError(
@@ -1,3 +1,4 @@
+use chrono::DateTime;
 use error_stack::ResultExt;
 use jj_lib::ref_name::RefNameBuf;
 use reqwest::header::{AUTHORIZATION, HeaderMap, USER_AGENT};
@@ -7,43 +8,22 @@ use url::Url;
 
 use crate::{
     error::{CustomError, Result},
-    pr::{Page, PageDirection, Pagination, PrFetcher},
+    pr::{Iteration, Page, PageDirection, Pagination, PrFetcher},
 };
 
 const GITHUB_GRAPHQL_URL: &str = "https://api.github.com/graphql";
-const DEFAULT_PAGE_SIZE: usize = 25;
-
 #[derive(Debug)]
 pub struct GithubFetcher {
     client: reqwest::blocking::Client,
     owner: String,
     repo: String,
     pr_id: usize,
+    page_size: usize,
 }
 
 impl GithubFetcher {
-    pub fn new(url: &Url, token: Option<String>) -> Result<Self> {
-        let mut headers = HeaderMap::new();
-        headers.insert(
-            USER_AGENT,
-            "graphql-client"
-                .parse()
-                .change_context(CustomError::UrlError)?,
-        );
-        if let Some(token) = &token {
-            headers.insert(
-                AUTHORIZATION,
-                format!("Bearer {}", token)
-                    .parse()
-                    .change_context(CustomError::UrlError)?,
-            );
-        }
-        let client = reqwest::blocking::Client::builder()
-            .default_headers(headers)
-            .build()
-            .change_context(CustomError::ProcessError(
-                "error building client".to_string(),
-            ))?;
+    pub fn new(url: &Url, token: Option<String>, page_size: usize) -> Result<Self> {
+        let client = client(token.as_deref())?;
         let segments: Vec<&str> = url.path_segments().ok_or(CustomError::UrlError)?.collect();
 
         match segments.as_slice() {
@@ -52,12 +32,95 @@ impl GithubFetcher {
                 owner: owner.to_string(),
                 repo: repo.to_string(),
                 pr_id: pr_id.parse().change_context(CustomError::UrlError)?,
+                page_size,
             }),
             _ => Err(CustomError::UrlError.into()),
         }
     }
 }
 
+fn client(token: Option<&str>) -> Result<reqwest::blocking::Client> {
+    let mut headers = HeaderMap::new();
+    headers.insert(
+        USER_AGENT,
+        "graphql-client"
+            .parse()
+            .change_context(CustomError::UrlError)?,
+    );
+    if let Some(token) = token {
+        headers.insert(
+            AUTHORIZATION,
+            format!("Bearer {}", token)
+                .parse()
+                .change_context(CustomError::UrlError)?,
+        );
+    }
+    reqwest::blocking::Client::builder()
+        .default_headers(headers)
+        .build()
+        .change_context(CustomError::ProcessError(
+            "error building client".to_string(),
+        ))
+}
+
+/// Returns the URL of the open pull request from `branch` into `owner/repo`, if there is one.
+pub fn find_pull_request(
+    owner: &str,
+    repo: &str,
+    branch: &str,
+    token: Option<&str>,
+) -> Result<Option<String>> {
+    let body = json!({
+        "query": include_str!("github_branch_query.graphql"),
+        "variables": {
+            "owner": owner,
+            "repo": repo,
+            "branch": branch,
+        }
+    });
+    let res: BranchQueryResponse = client(token)?
+        .post(GITHUB_GRAPHQL_URL)
+        .json(&body)
+        .send()
+        .change_context(CustomError::RequestError)?
+        .json()
+        .change_context(CustomError::RequestError)?;
+    Ok(res
+        .data
+        .repository
+        .pull_requests
+        .nodes
+        .into_iter()
+        .next()
+        .map(|pull_request| pull_request.url))
+}
+
+#[derive(Debug, Deserialize)]
+struct BranchQueryResponse {
+    data: BranchQueryData,
+}
+
+#[derive(Debug, Deserialize)]
+struct BranchQueryData {
+    repository: BranchQueryRepository,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct BranchQueryRepository {
+    pull_requests: PullRequestNodes,
+}
+
+#[derive(Debug, Deserialize)]
+struct PullRequestNodes {
+    nodes: Vec<PullRequestUrl>,
+}
+
+#[derive(Debug, Deserialize)]
+struct PullRequestUrl {
+    url: String,
+}
+
 #[derive(Debug, Deserialize)]
 pub struct GraphQlResponse {
     data: Data,
@@ -77,9 +140,16 @@ pub struct Repository {
 #[derive(Debug, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct PullRequest {
+    created_at: String,
+    author: Option<Actor>,
     timeline_items: TimelineItems,
 }
 
+#[derive(Debug, Deserialize)]
+pub struct Actor {
+    login: String,
+}
+
 #[derive(Debug, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct TimelineItems {
@@ -95,6 +165,8 @@ pub struct Edge {
 #[derive(Debug, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct Node {
+    created_at: String,
+    actor: Option<Actor>,
     before_commit: Commit,
     after_commit: Commit,
 }
@@ -111,37 +183,43 @@ pub struct PageInfo {
     start_cursor: Option<String>,
 }
 
-impl From<GraphQlResponse> for Page<RefNameBuf> {
+fn iteration(oid: &str, created_at: &str, actor: Option<&Actor>) -> Iteration {
+    Iteration {
+        name: RefNameBuf::from(oid),
+        pushed_at: DateTime::parse_from_rfc3339(created_at)
+            .ok()
+            .map(|date| date.to_utc()),
+        pusher: actor.map(|actor| actor.login.clone()),
+    }
+}
+
+impl From<GraphQlResponse> for Page<Iteration> {
     fn from(value: GraphQlResponse) -> Self {
-        let page_info = value.data.repository.pull_request.timeline_items.page_info;
+        let pull_request = &value.data.repository.pull_request;
+        let page_info = &pull_request.timeline_items.page_info;
         let mut commits = Vec::new();
-        for (i, entry) in value
-            .data
-            .repository
-            .pull_request
-            .timeline_items
-            .edges
-            .iter()
-            .enumerate()
-        {
+        for (i, entry) in pull_request.timeline_items.edges.iter().enumerate() {
             if !page_info.has_previous_page && i == 0 {
-                commits.push(RefNameBuf::from(&entry.node.before_commit.oid));
+                // The version before the first force push is assumed to be the opened one
+                commits.push(iteration(
+                    &entry.node.before_commit.oid,
+                    &pull_request.created_at,
+                    pull_request.author.as_ref(),
+                ));
             }
-            commits.push(RefNameBuf::from(&entry.node.after_commit.oid));
+            commits.push(iteration(
+                &entry.node.after_commit.oid,
+                &entry.node.created_at,
+                entry.node.actor.as_ref(),
+            ));
         }
 
         Self {
             items: commits,
             next: page_info.has_previous_page.then_some(Pagination::Cursor(
                 super::CursorPagination {
-                    cursor: page_info.start_cursor,
-                    limit: value
-                        .data
-                        .repository
-                        .pull_request
-                        .timeline_items
-                        .edges
-                        .len(),
+                    cursor: page_info.start_cursor.clone(),
+                    limit: pull_request.timeline_items.edges.len(),
                     direction: PageDirection::Backward,
                 },
             )),
@@ -151,9 +229,9 @@ impl From<GraphQlResponse> for Page<RefNameBuf> {
 }
 
 impl PrFetcher for GithubFetcher {
-    fn fetch_history(&self, pagination: Option<&Pagination>) -> Result<Page<RefNameBuf>> {
+    fn fetch_history(&self, pagination: Option<&Pagination>) -> Result<Page<Iteration>> {
         let (cursor, limit) = match pagination {
-            None => (None.as_ref(), DEFAULT_PAGE_SIZE),
+            None => (None.as_ref(), self.page_size),
             Some(Pagination::Cursor(pagination)) => (pagination.cursor.as_ref(), pagination.limit),
             _ => {
                 return Err(CustomError::ProcessError(
@@ -2,15 +2,19 @@ mod bitbucket;
 mod github;
 mod none;
 
+use chrono::{DateTime, Utc};
 use error_stack::ResultExt;
 use jj_lib::ref_name::RefNameBuf;
 use std::fmt::Debug;
 
 use crate::{
+    config::Config,
     error::{CustomError, Result},
     pr::{bitbucket::BitbucketFetcher, github::GithubFetcher, none::NoFetcher},
 };
 
+const DEFAULT_PAGE_SIZE: usize = 25;
+
 #[derive(Debug, Clone, Copy, Default)]
 pub enum PageDirection {
     #[default]
@@ -25,6 +29,35 @@ pub struct Page<T> {
     pub next: Option<Pagination>,
 }
 
+impl<T> Page<T> {
+    pub fn map<U>(self, f: impl FnMut(T) -> U) -> Page<U> {
+        Page {
+            items: self.items.into_iter().map(f).collect(),
+            direction: self.direction,
+            next: self.next,
+        }
+    }
+}
+
+/// One pushed version of a pull request.
+#[derive(Debug, Clone)]
+pub struct Iteration {
+    pub name: RefNameBuf,
+    /// When and by whom it was pushed, if the forge reports it
+    pub pushed_at: Option<DateTime<Utc>>,
+    pub pusher: Option<String>,
+}
+
+impl Iteration {
+    pub fn new(name: RefNameBuf) -> Self {
+        Self {
+            name,
+            pushed_at: None,
+            pusher: None,
+        }
+    }
+}
+
 #[derive(Debug, Clone, Copy, Default)]
 pub struct OffsetPagination {
     offset: usize,
@@ -55,13 +88,66 @@ impl Pagination {
 }
 
 pub trait PrFetcher: Debug + Send {
-    fn fetch_history(&self, pagination: Option<&Pagination>) -> Result<Page<RefNameBuf>>;
+    fn fetch_history(&self, pagination: Option<&Pagination>) -> Result<Page<Iteration>>;
+}
+
+/// Follows the pagination until the whole history is fetched, returned oldest first.
+pub fn fetch_all_history(fetcher: &dyn PrFetcher) -> Result<Vec<Iteration>> {
+    let mut history = Vec::new();
+    let mut pagination = None;
+    loop {
+        let page = fetcher.fetch_history(pagination.as_ref())?;
+        match page.direction {
+            PageDirection::Backward => {
+                history.splice(0..0, page.items);
+            }
+            PageDirection::Forward => history.extend(page.items),
+        }
+        let Some(next) = page.next else {
+            return Ok(history);
+        };
+        pagination = Some(next);
+    }
+}
+
+/// Looks up the open pull request from `branch` into the repo that `remote_url` points at.
+pub fn find_pr_url(remote_url: &gix::Url, branch: &str, config: &Config) -> Result<Option<String>> {
+    let Some(host) = remote_url.host() else {
+        return Ok(None);
+    };
+    let path = remote_url.path.to_string();
+    let path = path.trim_matches('/').trim_end_matches(".git");
+
+    if config.github.matches(host) {
+        let Some((owner, repo)) = path.split_once('/') else {
+            return Ok(None);
+        };
+        let token = config.github.token("GITHUB_TOKEN")?;
+        github::find_pull_request(owner, repo, branch, token.as_deref())
+    } else if config.bitbucket.matches(host) {
+        // Clones over http go through `/scm/`, over ssh the path starts with the project
+        let path = path.strip_prefix("scm/").unwrap_or(path);
+        let Some((project, repo)) = path.split_once('/') else {
+            return Ok(None);
+        };
+        let token = config.bitbucket.token("BITBUCKET_TOKEN")?;
+        bitbucket::find_pull_request(
+            &format!("https://{host}"),
+            project,
+            repo,
+            branch,
+            token.as_deref(),
+        )
+    } else {
+        Ok(None)
+    }
 }
 
 pub fn get_pr_fetcher(
     url: Option<String>,
     from: Option<String>,
     to: Option<String>,
+    config: &Config,
 ) -> Result<Option<Box<dyn PrFetcher>>> {
     match (url, from, to) {
         (None, Some(from), Some(to)) => Ok(Some(Box::new(NoFetcher::new(&from, &to)))),
@@ -69,20 +155,26 @@ pub fn get_pr_fetcher(
             let parsed = url::Url::parse(&url).change_context(CustomError::UrlError)?;
             let host = parsed.host_str().ok_or(CustomError::UrlError)?;
 
-            if host.contains("github.com") {
-                let token = std::env::var("GITHUB_TOKEN").ok();
+            if config.github.matches(host) {
+                let token = config.github.token("GITHUB_TOKEN")?;
                 if token.is_none() {
-                    println!(
+                    eprintln!(
                         "WARNING: GITHUB_TOKEN is not set, authentication might fail or you could run into rate limits!"
                     );
                 }
-                Ok(Some(Box::new(GithubFetcher::new(&parsed, token)?)))
-            } else if host.contains("bitbucket") {
-                let token = std::env::var("BITBUCKET_TOKEN").ok();
+                let page_size = config.github.page_size.unwrap_or(DEFAULT_PAGE_SIZE);
+                Ok(Some(Box::new(GithubFetcher::new(
+                    &parsed, token, page_size,
+                )?)))
+            } else if config.bitbucket.matches(host) {
+                let token = config.bitbucket.token("BITBUCKET_TOKEN")?;
                 if token.is_none() {
-                    println!("WARNING: BITBUCKET_TOKEN is not set, authentication might fail!");
+                    eprintln!("WARNING: BITBUCKET_TOKEN is not set, authentication might fail!");
                 }
-                Ok(Some(Box::new(BitbucketFetcher::new(&parsed, token)?)))
+                let page_size = config.bitbucket.page_size.unwrap_or(DEFAULT_PAGE_SIZE);
+                Ok(Some(Box::new(BitbucketFetcher::new(
+                    &parsed, token, page_size,
+                )?)))
             } else {
                 Ok(None)
             }
@@ -1,6 +1,6 @@
 use jj_lib::ref_name::RefNameBuf;
 
-use crate::pr::{Page, PageDirection, Pagination, PrFetcher};
+use crate::pr::{Iteration, Page, PageDirection, Pagination, PrFetcher};
 
 #[derive(Debug)]
 pub struct NoFetcher {
@@ -21,8 +21,11 @@ impl PrFetcher for NoFetcher {
     fn fetch_history(
         &self,
         _pagination: Option<&Pagination>,
-    ) -> crate::error::Result<Page<RefNameBuf>> {
-        let commits = vec![RefNameBuf::from(&self.from), RefNameBuf::from(&self.to)];
+    ) -> crate::error::Result<Page<Iteration>> {
+        let commits = vec![
+            Iteration::new(RefNameBuf::from(&self.from)),
+            Iteration::new(RefNameBuf::from(&self.to)),
+        ];
         Ok(Page {
             items: commits,
             direction: PageDirection::Backward,
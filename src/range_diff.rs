@@ -0,0 +1,143 @@
+use crate::{
+    diff::{DiffOptions, RenderFormat, render_tree_diff},
+    error::{CustomError, Result},
+    patch,
+    trees::DiffTree,
+};
+use error_stack::ResultExt;
+use jj_lib::{
+    commit::Commit, conflicts::ConflictMarkerStyle, object_id::ObjectId, repo::Repo,
+    workspace::Workspace,
+};
+
+/// Renders how the patch of a commit changed between two iterations, like `git range-diff`.
+///
+/// Every line of the result is prefixed with the outer diff marker, followed by the line of the
+/// patch itself, so `-+` is an addition that was dropped and `++` an addition that was introduced.
+pub fn render_range_diff(
+    trees: &DiffTree,
+    workspace: &Workspace,
+    repo: &impl Repo,
+    width: u16,
+    options: &DiffOptions,
+) -> Result<String> {
+    let (from, to) = match trees {
+        DiffTree::Interdiff { from, to } => (Some(*from), Some(*to)),
+        DiffTree::AddedCommit { commit } => (None, Some(*commit)),
+        DiffTree::RemovedCommit { commit } => (Some(*commit), None),
+    };
+    let old_patch = from
+        .map(|commit| commit_patch(commit, workspace, repo, width, options))
+        .transpose()?
+        .unwrap_or_default();
+    let new_patch = to
+        .map(|commit| commit_patch(commit, workspace, repo, width, options))
+        .transpose()?
+        .unwrap_or_default();
+
+    let body = patch::diff_lines(&old_patch.join("\n"), &new_patch.join("\n"));
+    let status = match (from, to) {
+        (Some(_), Some(_)) if body.iter().all(|line| line.starts_with(' ')) => '=',
+        (Some(_), Some(_)) => '!',
+        (Some(_), None) => '<',
+        (None, _) => '>',
+    };
+    let subject = to
+        .or(from)
+        .and_then(|commit| commit.description().lines().next())
+        .unwrap_or("<no message>");
+
+    let mut output = format!("{} {status} {} {subject}\n", short_id(from), short_id(to));
+    if status != '=' {
+        for line in body {
+            output.push_str("    ");
+            output.push_str(&line);
+            output.push('\n');
+        }
+    }
+    Ok(output)
+}
+
+fn short_id(commit: Option<&Commit>) -> String {
+    commit
+        .map(|commit| commit.id().hex()[..8].to_string())
+        .unwrap_or_else(|| "-".repeat(8))
+}
+
+/// Renders the patch of a single commit including its message, with everything that changes
+/// when the patch is merely moved around (blob ids, line numbers) stripped.
+fn commit_patch(
+    commit: &Commit,
+    workspace: &Workspace,
+    repo: &impl Repo,
+    width: u16,
+    options: &DiffOptions,
+) -> Result<Vec<String>> {
+    let parent_tree = commit
+        .parent_tree(repo)
+        .change_context(CustomError::RepoError)?;
+    let parent = commit
+        .parents()
+        .next()
+        .transpose()
+        .change_context(CustomError::CommitError(
+            "failed to get commit parents".to_string(),
+        ))?;
+    // Merges are diffed against their merged parents, copies from one parent don't apply
+    let copy_records = match parent {
+        Some(parent) if commit.parent_ids().len() == 1 => {
+            options.copy_records(&parent, commit, repo)?
+        }
+        _ => Default::default(),
+    };
+    let diff = render_tree_diff(
+        &parent_tree,
+        &commit.tree(),
+        &options.matcher(),
+        ConflictMarkerStyle::Git,
+        &copy_records,
+        &DiffOptions {
+            format: RenderFormat::Git,
+            ..options.clone()
+        },
+        workspace,
+        repo,
+        width,
+    )?;
+
+    let mut lines = vec!["## Commit message ##".to_string()];
+    lines.extend(
+        commit
+            .description()
+            .lines()
+            .map(|line| format!("    {line}")),
+    );
+
+    let mut path = "";
+    let mut in_hunk = false;
+    for line in diff.lines() {
+        if let Some(paths) = line.strip_prefix("diff --git ") {
+            path = paths
+                .rsplit_once(" b/")
+                .map(|(_, path)| path)
+                .unwrap_or(paths);
+            in_hunk = false;
+            lines.push(format!("## {path} ##"));
+        } else if line.starts_with("@@") {
+            in_hunk = true;
+            let context = line.splitn(3, "@@").nth(2).unwrap_or_default().trim();
+            if context.is_empty() {
+                lines.push(format!("@@ {path}"));
+            } else {
+                lines.push(format!("@@ {path}: {context}"));
+            }
+        } else if in_hunk
+            || !["index ", "--- ", "+++ "]
+                .iter()
+                .any(|header| line.starts_with(header))
+        {
+            lines.push(line.to_string());
+        }
+    }
+    Ok(lines)
+}
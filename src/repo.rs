@@ -1,4 +1,7 @@
-use crate::error::{CustomError, Result};
+use crate::{
+    config,
+    error::{CustomError, Result},
+};
 use error_stack::ResultExt;
 use jj_cli::{
     cli_util::{find_workspace_dir, start_repo_transaction},
@@ -9,13 +12,13 @@ use jj_lib::{
     config::{ConfigLayer, ConfigSource},
     git::{self, GitRefKind, GitSettings, parse_git_ref},
     git_backend::GitBackend,
-    local_working_copy::{LocalWorkingCopy, LocalWorkingCopyFactory},
     object_id::ObjectId,
     ref_name::RefNameBuf,
     repo::{ReadonlyRepo, Repo, StoreFactories},
     settings::UserSettings,
     workspace::{
-        DefaultWorkspaceLoaderFactory, WorkingCopyFactories, Workspace, WorkspaceLoaderFactory,
+        DefaultWorkspaceLoaderFactory, Workspace, WorkspaceLoaderFactory,
+        default_working_copy_factories,
     },
 };
 use std::{path::Path, sync::Arc};
@@ -27,12 +30,35 @@ pub struct RepoHandle {
     _tempdir: Option<TempDir>,
 }
 
+impl RepoHandle {
+    /// Wraps a repo loaded elsewhere, e.g. by jj when running as `jj diffsoup`.
+    pub fn new(workspace: Workspace, repo: Arc<ReadonlyRepo>) -> Self {
+        Self {
+            repo,
+            workspace,
+            _tempdir: None,
+        }
+    }
+}
+
+/// Opens the jj workspace or git repo containing `path`. Git repos without a jj workspace get a
+/// temporary one, running as `jj diffsoup` jj loads the workspace itself.
 pub fn open(path: &Path) -> Result<RepoHandle> {
-    let workspace_path = path.join(".jj");
-    if !workspace_path.exists() {
+    let workspace_dir = find_workspace_dir(path);
+    if !workspace_dir.join(".jj").is_dir() {
         return init_jj_repo(path);
     };
-    let workspace = load_jj_repo(path)?;
+    let loader = DefaultWorkspaceLoaderFactory
+        .create(workspace_dir)
+        .change_context(CustomError::RepoError)?;
+    let settings = load_settings(loader.repo_path(), loader.workspace_root(), None)?;
+    let workspace = loader
+        .load(
+            &settings,
+            &StoreFactories::default(),
+            &default_working_copy_factories(),
+        )
+        .change_context(CustomError::RepoError)?;
     let repo = workspace
         .repo_loader()
         .load_at_head()
@@ -44,47 +70,13 @@ pub fn open(path: &Path) -> Result<RepoHandle> {
     })
 }
 
-fn load_jj_repo(path: &Path) -> Result<Workspace> {
-    let mut raw_config = config_from_environment(default_config_layers());
-    let mut config_env = ConfigEnv::from_environment();
-    let loader = DefaultWorkspaceLoaderFactory
-        .create(find_workspace_dir(path))
-        .change_context(CustomError::RepoError)?;
-    config_env.reset_repo_path(loader.repo_path());
-    config_env
-        .reload_repo_config(&mut raw_config)
-        .change_context(CustomError::ConfigError)?;
-    config_env.reset_workspace_path(loader.workspace_root());
-    config_env
-        .reload_workspace_config(&mut raw_config)
-        .change_context(CustomError::ConfigError)?;
-    let config = config_env
-        .resolve_config(&raw_config)
-        .change_context(CustomError::RepoError)?;
-    let mut store_factories = StoreFactories::default();
-    store_factories.add_backend(
-        GitBackend::name(),
-        Box::new(|settings, store_path| Ok(Box::new(GitBackend::load(settings, store_path)?))),
-    );
-    let mut working_copy_factories = WorkingCopyFactories::new();
-    working_copy_factories.insert(
-        LocalWorkingCopy::name().to_owned(),
-        Box::new(LocalWorkingCopyFactory {}),
-    );
-    let settings = UserSettings::from_config(config).change_context(CustomError::RepoError)?;
-
-    loader
-        .load(&settings, &store_factories, &working_copy_factories)
-        .change_context(CustomError::RepoError)
-}
-
 fn init_jj_repo(git_repo_path: &Path) -> Result<RepoHandle> {
     let git_repo_path = git_repo_path
         .canonicalize()
         .change_context(CustomError::RepoError)
         .attach("failed to resolve repository path")?;
 
-    let git_repo = gix::open(&git_repo_path).change_context(CustomError::RepoError)?;
+    let git_repo = gix::discover(&git_repo_path).change_context(CustomError::RepoError)?;
     let trunk_alias = get_trunk_alias(&git_repo)?;
 
     let workspace_root = TempDir::new()
@@ -92,28 +84,17 @@ fn init_jj_repo(git_repo_path: &Path) -> Result<RepoHandle> {
         .attach("could not create dir for jj workspace")?;
     let repo_path = workspace_root.path().join(".jj/repo");
 
-    let mut raw_config = config_from_environment(default_config_layers());
-    if let Some(ref symbol) = trunk_alias {
-        let mut layer = ConfigLayer::empty(ConfigSource::User);
-        layer
-            .set_value("revset-aliases.\"trunk()\"", symbol.to_string())
-            .change_context(CustomError::ConfigError)?;
-        raw_config.as_mut().add_layer(layer);
-    }
-
-    let mut config_env = ConfigEnv::from_environment();
-    config_env.reset_repo_path(&repo_path);
-    config_env
-        .reload_repo_config(&mut raw_config)
-        .change_context(CustomError::ConfigError)?;
-    config_env.reset_workspace_path(workspace_root.path());
-    config_env
-        .reload_workspace_config(&mut raw_config)
-        .change_context(CustomError::ConfigError)?;
-    let config = config_env
-        .resolve_config(&raw_config)
-        .change_context(CustomError::RepoError)?;
-    let settings = UserSettings::from_config(config).change_context(CustomError::RepoError)?;
+    let trunk_layer = match trunk_alias {
+        Some(symbol) => {
+            let mut layer = ConfigLayer::empty(ConfigSource::User);
+            layer
+                .set_value("revset-aliases.\"trunk()\"", symbol)
+                .change_context(CustomError::ConfigError)?;
+            Some(layer)
+        }
+        None => None,
+    };
+    let settings = load_settings(&repo_path, workspace_root.path(), trunk_layer)?;
 
     let (workspace, repo) =
         Workspace::init_external_git(&settings, workspace_root.path(), git_repo.path())
@@ -136,6 +117,79 @@ fn init_jj_repo(git_repo_path: &Path) -> Result<RepoHandle> {
     })
 }
 
+/// Resolves the jj config of a workspace the way jj does, with `extra_layer` on top of the user
+/// config.
+fn load_settings(
+    repo_path: &Path,
+    workspace_root: &Path,
+    extra_layer: Option<ConfigLayer>,
+) -> Result<UserSettings> {
+    let mut raw_config = config_from_environment(config_layers());
+    if let Some(layer) = extra_layer {
+        raw_config.as_mut().add_layer(layer);
+    }
+    let mut config_env = ConfigEnv::from_environment();
+    config_env.reset_repo_path(repo_path);
+    config_env
+        .reload_repo_config(&mut raw_config)
+        .change_context(CustomError::ConfigError)?;
+    config_env.reset_workspace_path(workspace_root);
+    config_env
+        .reload_workspace_config(&mut raw_config)
+        .change_context(CustomError::ConfigError)?;
+    let config = config_env
+        .resolve_config(&raw_config)
+        .change_context(CustomError::RepoError)?;
+    UserSettings::from_config(config).change_context(CustomError::RepoError)
+}
+
+/// jj's default config, followed by diffsoup's.
+fn config_layers() -> Vec<ConfigLayer> {
+    let mut layers = default_config_layers();
+    layers.push(config::default_layer());
+    layers
+}
+
+/// Returns a bookmark on the working copy commit or its parents, falling back to git's current
+/// branch for repos without a jj workspace.
+pub fn current_bookmark(workspace: &Workspace, repo: &ReadonlyRepo) -> Result<Option<String>> {
+    let view = repo.view();
+    if let Some(wc_commit_id) = view.get_wc_commit_id(workspace.workspace_name()) {
+        let wc_commit = repo
+            .store()
+            .get_commit(wc_commit_id)
+            .change_context(CustomError::RepoError)?;
+        for commit_id in [wc_commit_id].into_iter().chain(wc_commit.parent_ids()) {
+            if let Some((name, _)) = view.local_bookmarks_for_commit(commit_id).next() {
+                return Ok(Some(name.as_str().to_string()));
+            }
+        }
+    }
+
+    let Some(git_backend) = repo.store().backend_impl::<GitBackend>() else {
+        return Ok(None);
+    };
+    let head = git_backend
+        .git_repo()
+        .head_name()
+        .change_context(CustomError::RepoError)?;
+    Ok(head.map(|name| name.shorten().to_string()))
+}
+
+/// URLs of the `upstream` and `origin` remotes, in that order, which is where a pull request
+/// from a fork would be opened.
+pub fn remote_urls(repo: &ReadonlyRepo) -> Result<Vec<gix::Url>> {
+    let Some(git_backend) = repo.store().backend_impl::<GitBackend>() else {
+        return Err(CustomError::CommitError("not backed by a git repo".to_string()).into());
+    };
+    let git_repo = git_backend.git_repo();
+    Ok(["upstream", "origin"]
+        .into_iter()
+        .filter_map(|name| git_repo.find_remote(name).ok())
+        .filter_map(|remote| remote.url(gix::remote::Direction::Fetch).cloned())
+        .collect())
+}
+
 fn get_trunk_alias(repo: &gix::Repository) -> Result<Option<String>> {
     for remote in ["upstream", "origin"] {
         let ref_name = format!("refs/remotes/{remote}/HEAD");
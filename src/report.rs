@@ -0,0 +1,79 @@
+use std::{fs, path::PathBuf, sync::Arc};
+
+use diffsoup::{
+    diff::{DiffOptions, RenderFormat, calculate_branch_diff},
+    html::{ReportSection, render_report},
+    pr::{PrFetcher, fetch_all_history},
+};
+use jj_lib::{repo::ReadonlyRepo, workspace::Workspace};
+
+use crate::{DiffArgs, fetch_missing, show};
+
+#[derive(clap::Args, Debug)]
+pub struct ReportArgs {
+    #[command(flatten)]
+    pub diff: DiffArgs,
+
+    /// HTML file to write
+    #[arg(short, long, value_name = "FILE")]
+    output: PathBuf,
+}
+
+/// Writes a single HTML file comparing every pair of patchsets.
+pub fn run(
+    workspace: Workspace,
+    repo: Arc<ReadonlyRepo>,
+    pr_fetcher: Box<dyn PrFetcher>,
+    options: DiffOptions,
+    args: &ReportArgs,
+) -> Result<(), Box<dyn std::error::Error>> {
+    // The other formats contain ANSI colors or are meant for terminals
+    let options = DiffOptions {
+        format: RenderFormat::Git,
+        ..options
+    };
+    let history = fetch_all_history(pr_fetcher.as_ref())?;
+    let repo = fetch_missing(history.iter().map(|iteration| &iteration.name), repo)?;
+
+    let mut sections = Vec::new();
+    for to in 1..history.len() {
+        for from in 0..to {
+            eprintln!("Comparing patchset {} to {}...", from + 1, to + 1);
+            let commits = calculate_branch_diff(
+                history[from].name.as_str(),
+                history[to].name.as_str(),
+                &workspace,
+                repo.as_ref(),
+                &options,
+            )?;
+            let mut rendered = Vec::new();
+            for commit in commits {
+                let interdiff = if commit.has_changes() {
+                    show::render(&commit, &workspace, repo.as_ref(), 120, &options)?
+                        .map(|(_, interdiff)| interdiff)
+                } else {
+                    None
+                };
+                rendered.push((commit, interdiff));
+            }
+            sections.push(ReportSection {
+                from,
+                to,
+                commits: rendered,
+            });
+        }
+    }
+
+    let patchsets: Vec<String> = history
+        .iter()
+        .map(|iteration| iteration.name.as_str().to_string())
+        .collect();
+    let title = args
+        .diff
+        .pr
+        .pr_url
+        .clone()
+        .unwrap_or_else(|| patchsets.join(" → "));
+    fs::write(&args.output, render_report(&title, &patchsets, &sections))?;
+    Ok(())
+}
@@ -0,0 +1,425 @@
+use std::{
+    io::{self, IsTerminal, Write},
+    process::{Command, Stdio},
+    sync::Arc,
+};
+
+use clap::{ColorChoice, ValueEnum};
+use diffsoup::{
+    diff::{
+        CommitDiff, DiffOptions, RenderFormat, RenderedInterdiff, calculate_branch_diff,
+        get_commit, short_id,
+    },
+    json, markdown,
+    pr::{Iteration, PrFetcher, fetch_all_history},
+    trees::DiffTree,
+};
+use jj_lib::{repo::ReadonlyRepo, settings::UserSettings, workspace::Workspace};
+
+use crate::{DiffArgs, ansi, fetch_missing};
+
+const RESET: &str = "\x1b[0m";
+const RED: &str = "\x1b[31m";
+const GREEN: &str = "\x1b[32m";
+const YELLOW: &str = "\x1b[33m";
+const CYAN: &str = "\x1b[36m";
+const MAGENTA: &str = "\x1b[35m";
+const BOLD_YELLOW: &str = "\x1b[1;33m";
+const BOLD_CYAN: &str = "\x1b[1;36m";
+
+#[derive(clap::Args, Debug)]
+pub struct ShowArgs {
+    #[command(flatten)]
+    pub diff: DiffArgs,
+
+    #[command(flatten)]
+    patchsets: PatchsetArgs,
+
+    /// Output format, `json` follows a versioned schema for use by other tools and `markdown`
+    /// is meant for PR comments
+    #[arg(long, value_enum, value_name = "FORMAT", default_value_t = OutputFormat::Text)]
+    format: OutputFormat,
+
+    /// Include the diff hunks of every file in the JSON output
+    #[arg(long)]
+    hunks: bool,
+}
+
+// How `show` writes to the terminal. jj has these as global flags, so they are only added when
+// running on our own and come from jj's `Ui` as `jj diffsoup`.
+#[derive(clap::Args, Debug, Clone)]
+pub struct OutputArgs {
+    /// When to color the output of `show`, defaults to jj's `ui.color`
+    #[arg(long, global = true, value_name = "WHEN", default_value_t = ColorChoice::Auto)]
+    pub color: ColorChoice,
+
+    /// Write the output of `show` to stdout directly instead of through jj's `ui.pager`, which
+    /// defaults to `$PAGER`
+    #[arg(long, global = true)]
+    pub no_pager: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum OutputFormat {
+    Text,
+    Json,
+    /// Summary for pasting into a PR comment
+    Markdown,
+}
+
+// The two patchsets to compare, numbered like in `diffsoup iterations`
+#[derive(clap::Args, Debug)]
+pub struct PatchsetArgs {
+    /// Patchset to compare from, counting from 1, defaults to the one before `--to-ps`
+    #[arg(long, value_name = "N")]
+    from_ps: Option<usize>,
+
+    /// Patchset to compare to, counting from 1, defaults to the latest
+    #[arg(long, value_name = "N")]
+    to_ps: Option<usize>,
+}
+
+/// The commits of two patchsets, matched up with each other.
+pub struct BranchComparison {
+    /// All patchsets of the PR, oldest first
+    pub history: Vec<Iteration>,
+    pub from_index: usize,
+    pub to_index: usize,
+    /// The repo after fetching any missing commits
+    pub repo: Arc<ReadonlyRepo>,
+    pub commits: Vec<CommitDiff>,
+}
+
+impl PatchsetArgs {
+    /// Fetches the PR history and the selected patchsets, and matches up their commits.
+    pub fn compare(
+        &self,
+        workspace: &Workspace,
+        repo: Arc<ReadonlyRepo>,
+        pr_fetcher: &dyn PrFetcher,
+        options: &DiffOptions,
+    ) -> Result<BranchComparison, Box<dyn std::error::Error>> {
+        let history = fetch_all_history(pr_fetcher)?;
+        let (from_index, to_index) = self.select(history.len())?;
+        let (from, to) = (&history[from_index].name, &history[to_index].name);
+
+        let repo = fetch_missing([from, to].into_iter(), repo)?;
+        let commits = calculate_branch_diff(
+            from.as_str(),
+            to.as_str(),
+            workspace,
+            repo.as_ref(),
+            options,
+        )?;
+        Ok(BranchComparison {
+            history,
+            from_index,
+            to_index,
+            repo,
+            commits,
+        })
+    }
+
+    /// Converts the 1-based patchset numbers into indices of the history.
+    fn select(&self, count: usize) -> Result<(usize, usize), String> {
+        if count == 0 {
+            return Err("no patchsets found".to_string());
+        }
+        let to = self.to_ps.unwrap_or(count);
+        let from = self.from_ps.unwrap_or(to.saturating_sub(1).max(1));
+        if from == 0 || to > count || from > to {
+            return Err(format!(
+                "patchsets must satisfy 1 <= --from-ps <= --to-ps <= {count}, got {from} and {to}"
+            ));
+        }
+        Ok((from - 1, to - 1))
+    }
+}
+
+/// Prints the interdiff of every changed commit between two patchsets.
+pub fn run(
+    workspace: Workspace,
+    repo: Arc<ReadonlyRepo>,
+    pr_fetcher: Box<dyn PrFetcher>,
+    options: DiffOptions,
+    args: &ShowArgs,
+    output_args: &OutputArgs,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let BranchComparison {
+        history,
+        from_index,
+        to_index,
+        repo,
+        commits,
+    } = args
+        .patchsets
+        .compare(&workspace, repo, pr_fetcher.as_ref(), &options)?;
+    let width = crossterm::terminal::size()
+        .map(|(width, _)| width)
+        .unwrap_or(80);
+
+    if args.format == OutputFormat::Json {
+        // Hunks are always taken from a plain git diff, regardless of the chosen diff format
+        let git_options = DiffOptions {
+            format: RenderFormat::Git,
+            ..options
+        };
+        let mut entries = Vec::new();
+        for commit in &commits {
+            let interdiff = if args.hunks && commit.has_changes() {
+                render(commit, &workspace, repo.as_ref(), width, &git_options)?
+                    .map(|(_, interdiff)| interdiff)
+            } else {
+                None
+            };
+            entries.push(json::Commit::new(commit, interdiff.as_ref()));
+        }
+        let patchsets = history
+            .iter()
+            .map(|iteration| iteration.name.as_str().to_string())
+            .collect();
+        let comparison = json::Comparison::new(patchsets, from_index, to_index, entries);
+        let mut output = serde_json::to_string_pretty(&comparison)?;
+        output.push('\n');
+        write_output(&output, None)?;
+        return Ok(());
+    }
+
+    if args.format == OutputFormat::Markdown {
+        let git_options = DiffOptions {
+            format: RenderFormat::Git,
+            ..options
+        };
+        let mut rendered = Vec::new();
+        for commit in commits {
+            let interdiff = if commit.has_changes() {
+                render(&commit, &workspace, repo.as_ref(), width, &git_options)?
+                    .map(|(_, interdiff)| interdiff)
+            } else {
+                None
+            };
+            rendered.push((commit, interdiff));
+        }
+        let name = |index: usize| {
+            let name = history[index].name.as_str();
+            format!("{} ({})", index + 1, short_id(name))
+        };
+        let output = markdown::render_summary(&name(from_index), &name(to_index), &rendered);
+        write_output(&output, None)?;
+        return Ok(());
+    }
+
+    let settings = workspace.settings();
+    let color = match (
+        output_args.color,
+        settings.get_string("ui.color").as_deref(),
+    ) {
+        (ColorChoice::Always, _) | (ColorChoice::Auto, Ok("always" | "debug")) => true,
+        (ColorChoice::Never, _) | (ColorChoice::Auto, Ok("never")) => false,
+        (ColorChoice::Auto, _) => io::stdout().is_terminal(),
+    };
+    let pager = if output_args.no_pager {
+        None
+    } else {
+        pager_command(settings)
+    };
+    let mut output = String::new();
+    for commit in commits.iter().filter(|commit| commit.has_changes()) {
+        let Some((title, interdiff)) = render(commit, &workspace, repo.as_ref(), width, &options)?
+        else {
+            continue;
+        };
+        write_interdiff(&mut output, &title, &interdiff, options.format, color);
+    }
+
+    write_output(&output, pager)?;
+    Ok(())
+}
+
+/// Renders the interdiff of one list entry, along with its title.
+pub fn render(
+    commit: &CommitDiff,
+    workspace: &Workspace,
+    repo: &ReadonlyRepo,
+    width: u16,
+    options: &DiffOptions,
+) -> Result<Option<(String, RenderedInterdiff)>, Box<dyn std::error::Error>> {
+    let from_commit = commit
+        .from
+        .as_ref()
+        .map(|meta| get_commit(&meta.sha, workspace, repo))
+        .transpose()?;
+    let to_commit = commit
+        .to
+        .as_ref()
+        .map(|meta| get_commit(&meta.sha, workspace, repo))
+        .transpose()?;
+    let Some(tree) = DiffTree::from(from_commit.as_ref(), to_commit.as_ref()) else {
+        return Ok(None);
+    };
+    let interdiff = diffsoup::diff::render_interdiff(&tree, workspace, repo, width, options)?;
+    Ok(Some((format!("{tree}"), interdiff)))
+}
+
+fn write_interdiff(
+    output: &mut String,
+    title: &str,
+    interdiff: &RenderedInterdiff,
+    format: RenderFormat,
+    color: bool,
+) {
+    let mut push_line = |line: &str, style: Option<&str>| {
+        match style.filter(|_| color) {
+            Some(style) => output.push_str(&format!("{style}{line}{RESET}")),
+            None => output.push_str(line),
+        }
+        output.push('\n');
+    };
+
+    push_line(&format!("Interdiff {title}"), Some(BOLD_YELLOW));
+    match interdiff.conflicts.len() {
+        0 => {}
+        1 => push_line("1 conflicted file, interdiff is approximate", Some(MAGENTA)),
+        n => push_line(
+            &format!("{n} conflicted files, interdiff is approximate"),
+            Some(MAGENTA),
+        ),
+    }
+    push_line("", None);
+
+    for (title, section) in interdiff.sections() {
+        push_line(title, Some(BOLD_CYAN));
+        for line in section.lines() {
+            push_line(line, diff_line_style(line));
+        }
+        push_line("", None);
+    }
+
+    match format {
+        // Already colored by jj or the tool
+        RenderFormat::ColorWords | RenderFormat::External if color => {
+            for line in interdiff.diff.lines() {
+                push_line(line, None);
+            }
+        }
+        RenderFormat::ColorWords | RenderFormat::External => {
+            for line in ansi::strip(&interdiff.diff).lines() {
+                push_line(line, None);
+            }
+        }
+        RenderFormat::Git => {
+            for line in interdiff.diff.lines() {
+                push_line(line, diff_line_style(line));
+            }
+        }
+        RenderFormat::RangeDiff => {
+            for line in interdiff.diff.lines() {
+                push_line(line, range_diff_line_style(line));
+            }
+        }
+    }
+    push_line("", None);
+}
+
+fn diff_line_style(line: &str) -> Option<&'static str> {
+    if line.starts_with('+') && !line.starts_with("+++") {
+        Some(GREEN)
+    } else if line.starts_with('-') && !line.starts_with("---") {
+        Some(RED)
+    } else if line.starts_with("@@") {
+        Some(CYAN)
+    } else if line.starts_with("diff") || line.starts_with("index") {
+        Some(YELLOW)
+    } else {
+        None
+    }
+}
+
+/// Colors range-diff lines by whether the patch line was dropped or introduced.
+fn range_diff_line_style(line: &str) -> Option<&'static str> {
+    match line.strip_prefix("    ") {
+        None => Some(BOLD_YELLOW),
+        Some(content) if content.starts_with('+') => Some(GREEN),
+        Some(content) if content.starts_with('-') => Some(RED),
+        Some(_) => None,
+    }
+}
+
+/// The command from jj's `ui.pager`, which defaults to `$PAGER`, or `None` if `ui.paginate` is
+/// `never`.
+fn pager_command(settings: &UserSettings) -> Option<Vec<String>> {
+    if settings
+        .get_string("ui.paginate")
+        .is_ok_and(|paginate| paginate == "never")
+    {
+        return None;
+    }
+    let command = settings
+        .get::<Vec<String>>("ui.pager.command")
+        .or_else(|_| settings.get::<Vec<String>>("ui.pager"))
+        .or_else(|_| {
+            settings
+                .get_string("ui.pager")
+                .map(|pager| pager.split_whitespace().map(str::to_string).collect())
+        })
+        .unwrap_or_else(|_| vec!["less".to_string(), "-FRX".to_string()]);
+    Some(command)
+}
+
+/// Writes through the pager when stdout is a terminal.
+fn write_output(output: &str, pager: Option<Vec<String>>) -> io::Result<()> {
+    if let Some(pager) = pager.filter(|_| io::stdout().is_terminal())
+        && let Some((program, args)) = pager.split_first()
+        && let Ok(mut child) = Command::new(program)
+            .args(args)
+            .stdin(Stdio::piped())
+            .spawn()
+    {
+        if let Some(mut stdin) = child.stdin.take() {
+            // Quitting the pager before reading everything is not an error
+            let _ = stdin.write_all(output.as_bytes());
+        }
+        child.wait()?;
+        return Ok(());
+    }
+
+    match io::stdout().lock().write_all(output.as_bytes()) {
+        Err(e) if e.kind() == io::ErrorKind::BrokenPipe => Ok(()),
+        result => result,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn select(
+        from_ps: Option<usize>,
+        to_ps: Option<usize>,
+        count: usize,
+    ) -> Option<(usize, usize)> {
+        PatchsetArgs { from_ps, to_ps }.select(count).ok()
+    }
+
+    #[test]
+    fn select_defaults_to_the_last_two_patchsets() {
+        assert_eq!(select(None, None, 3), Some((1, 2)));
+        assert_eq!(select(None, Some(2), 3), Some((0, 1)));
+        assert_eq!(select(Some(1), None, 3), Some((0, 2)));
+    }
+
+    #[test]
+    fn select_compares_a_single_patchset_with_itself() {
+        assert_eq!(select(None, None, 1), Some((0, 0)));
+        assert_eq!(select(Some(2), Some(2), 3), Some((1, 1)));
+    }
+
+    #[test]
+    fn select_rejects_invalid_patchsets() {
+        assert_eq!(select(None, None, 0), None);
+        assert_eq!(select(Some(0), None, 3), None);
+        assert_eq!(select(None, Some(4), 3), None);
+        assert_eq!(select(Some(3), Some(2), 3), None);
+        assert_eq!(select(None, Some(0), 3), None);
+    }
+}
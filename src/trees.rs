@@ -1,16 +1,8 @@
-use std::{fmt::Display, path::Path};
+use std::fmt::Display;
 
 use crate::error::{CustomError, Result};
 use error_stack::ResultExt;
-use jj_lib::{
-    backend::{CopyId, TreeValue},
-    commit::Commit,
-    merge::Merge,
-    merged_tree::{MergedTree, MergedTreeBuilder},
-    repo::Repo,
-    repo_path::RepoPathBuf,
-    rewrite::rebase_to_dest_parent,
-};
+use jj_lib::{commit::Commit, merged_tree::MergedTree, repo::Repo, rewrite::rebase_to_dest_parent};
 
 #[derive(Debug)]
 pub enum DiffTree<'a> {
@@ -36,74 +28,77 @@ impl DiffTree<'_> {
 impl Display for DiffTree<'_> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
-            Self::Interdiff { from, to } => write!(f, "{} -> {}", from.id(), to.id()),
-            Self::AddedCommit { commit } => write!(f, "{} (new)", commit.id()),
-            Self::RemovedCommit { commit } => write!(f, "{} (removed)", commit.id()),
+            Self::Interdiff { from, to } => write!(f, "{} -> {}", from.id(), to.id())?,
+            Self::AddedCommit { commit } => write!(f, "{} (new)", commit.id())?,
+            Self::RemovedCommit { commit } => write!(f, "{} (removed)", commit.id())?,
         }
+        if self.is_merge() {
+            write!(f, " [merge, conflict resolution vs. merged parents]")?;
+        }
+        Ok(())
     }
 }
 
-fn write_virtual_tree(
-    description: &str,
-    tree: &MergedTree,
-    repo: &impl Repo,
-) -> Result<MergedTree> {
-    const COMMIT_DESCRIPTION_PATH: &str = ".__COMMIT_MESSAGE__";
-    let path = RepoPathBuf::from_relative_path(Path::new(COMMIT_DESCRIPTION_PATH))
-        .change_context(CustomError::RepoError)?;
-    let blob_id =
-        futures::executor::block_on(repo.store().write_file(&path, &mut description.as_bytes()))
-            .change_context(CustomError::ProcessError(
-                "failed to block on store write".to_string(),
-            ))?;
-
-    let mut virtual_tree = MergedTreeBuilder::new(tree.clone());
-    virtual_tree.set_or_remove(
-        path,
-        Merge::normal(TreeValue::File {
-            id: blob_id,
-            executable: false,
-            copy_id: CopyId::placeholder(),
-        }),
-    );
-    virtual_tree
-        .write_tree()
-        .change_context(CustomError::RepoError)
-}
-
 impl DiffTree<'_> {
+    /// Whether the diff is of a merge commit, whose trees are compared against the automatic
+    /// merge of its parents rather than a single parent.
+    pub fn is_merge(&self) -> bool {
+        match self {
+            Self::Interdiff { to: commit, .. }
+            | Self::AddedCommit { commit }
+            | Self::RemovedCommit { commit } => commit.parent_ids().len() > 1,
+        }
+    }
+
     pub fn get_trees(&self, repo: &impl Repo) -> Result<(MergedTree, MergedTree)> {
         match self {
             Self::Interdiff { from, to } => {
-                let rebased = rebase_to_dest_parent(repo, std::slice::from_ref(from), to)
+                let from_tree = rebase_to_dest_parent(repo, std::slice::from_ref(from), to)
                     .change_context(CustomError::RepoError)?;
-                let (from_tree, to_tree) = if from.description() == to.description() {
-                    (rebased, to.tree())
-                } else {
-                    (
-                        write_virtual_tree(from.description(), &rebased, repo)?,
-                        write_virtual_tree(to.description(), &to.tree(), repo)?,
-                    )
-                };
 
-                Ok((from_tree, to_tree))
+                Ok((from_tree, to.tree()))
             }
             Self::AddedCommit { commit } => {
                 let from_tree = commit
                     .parent_tree(repo)
                     .change_context(CustomError::RepoError)?;
-                let to_tree = write_virtual_tree(commit.description(), &commit.tree(), repo)?;
 
-                Ok((from_tree, to_tree))
+                Ok((from_tree, commit.tree()))
             }
             Self::RemovedCommit { commit } => {
-                let from_tree = write_virtual_tree(commit.description(), &commit.tree(), repo)?;
                 let to_tree = commit
                     .parent_tree(repo)
                     .change_context(CustomError::RepoError)?;
 
-                Ok((from_tree, to_tree))
+                Ok((commit.tree(), to_tree))
+            }
+        }
+    }
+
+    /// Returns the old and new commit description, if they differ.
+    pub fn get_descriptions(&self) -> Option<(&str, &str)> {
+        match self {
+            Self::Interdiff { from, to } if from.description() == to.description() => None,
+            Self::Interdiff { from, to } => Some((from.description(), to.description())),
+            Self::AddedCommit { commit } => Some(("", commit.description())),
+            Self::RemovedCommit { commit } => Some((commit.description(), "")),
+        }
+    }
+
+    /// Returns the trees of the old and new base for an interdiff, i.e. the changes that came
+    /// in from upstream between the two iterations.
+    pub fn get_base_trees(&self, repo: &impl Repo) -> Result<Option<(MergedTree, MergedTree)>> {
+        match self {
+            Self::Interdiff { from, to } => {
+                let from_tree = from
+                    .parent_tree(repo)
+                    .change_context(CustomError::RepoError)?;
+                let to_tree = to
+                    .parent_tree(repo)
+                    .change_context(CustomError::RepoError)?;
+                Ok(Some((from_tree, to_tree)))
             }
+            Self::AddedCommit { .. } | Self::RemovedCommit { .. } => Ok(None),
         }
     }
 }
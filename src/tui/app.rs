@@ -6,7 +6,11 @@ use crossterm::{
     execute,
     terminal::{EnterAlternateScreen, LeaveAlternateScreen, disable_raw_mode, enable_raw_mode},
 };
-use diffsoup::diff::CommitDiff;
+use diffsoup::{
+    diff::{CommitDiff, CommitMeta, CommitStatus, FileStats, RenderFormat},
+    external::ExternalDiff,
+    patch::{self, SideBySideRow},
+};
 use ratatui::{
     Terminal,
     backend::CrosstermBackend,
@@ -15,16 +19,26 @@ use ratatui::{
     text::{Line, Span},
     widgets::{Block, Borders, List, ListItem, Paragraph},
 };
-use std::{io, sync::mpsc::Receiver, thread::JoinHandle, time::Duration};
+use std::{
+    io,
+    sync::{Arc, mpsc::Receiver},
+    thread::JoinHandle,
+    time::Duration,
+};
 
-use crate::tui::{
-    UiSender,
-    state::{AppScreen, ScrollDirection, ScrollEvent, UiEvent},
+use crate::{
+    ansi,
+    tui::{
+        UiSender,
+        settings::{KeyBindings, theme},
+        state::{AppScreen, ScrollDirection, ScrollEvent, UiEvent},
+    },
 };
 
 pub fn spawn_ui_thread(
     action_tx: UiSender,
     view_rx: Receiver<AppScreen>,
+    keys: KeyBindings,
 ) -> JoinHandle<anyhow::Result<()>> {
     let mut screen = AppScreen::Loading(None);
     std::thread::spawn(move || {
@@ -45,7 +59,7 @@ pub fn spawn_ui_thread(
                         action_tx.send(UiEvent::SizeChange((width, height)))?;
                     }
                     Event::Key(event) => {
-                        if let Some(action) = handle_event(&event, &screen) {
+                        if let Some(action) = handle_event(&event, &screen, &keys) {
                             action_tx.send(action)?;
                         }
                     }
@@ -54,7 +68,18 @@ pub fn spawn_ui_thread(
             }
 
             if let Ok(view) = view_rx.try_recv() {
-                terminal.draw(|f| draw(&view, f))?;
+                match (&view, &screen) {
+                    // The same screen is resent on unrelated events while the tool runs
+                    (AppScreen::External(external_diff), AppScreen::External(running))
+                        if Arc::ptr_eq(external_diff, running) => {}
+                    (AppScreen::External(external_diff), _) => {
+                        let result = run_external_tool(&mut terminal, external_diff);
+                        action_tx.send(UiEvent::ExternalToolExited(result.err()))?;
+                    }
+                    _ => {
+                        terminal.draw(|f| draw(&view, f, &keys))?;
+                    }
+                }
                 screen = view;
             }
         }
@@ -70,30 +95,62 @@ pub fn spawn_ui_thread(
     })
 }
 
-fn handle_event(event: &KeyEvent, screen: &AppScreen) -> Option<UiEvent> {
+/// Hands the terminal over to the external tool until it exits.
+fn run_external_tool(
+    terminal: &mut Terminal<CrosstermBackend<io::Stdout>>,
+    external_diff: &ExternalDiff,
+) -> Result<(), String> {
+    let width = terminal.size().map_err(|e| e.to_string())?.width;
+    let suspend = |terminal: &mut Terminal<CrosstermBackend<io::Stdout>>| -> io::Result<()> {
+        disable_raw_mode()?;
+        execute!(
+            terminal.backend_mut(),
+            LeaveAlternateScreen,
+            DisableMouseCapture
+        )
+    };
+    let resume = |terminal: &mut Terminal<CrosstermBackend<io::Stdout>>| -> io::Result<()> {
+        enable_raw_mode()?;
+        execute!(
+            terminal.backend_mut(),
+            EnterAlternateScreen,
+            EnableMouseCapture
+        )?;
+        terminal.clear()
+    };
+
+    suspend(terminal).map_err(|e| e.to_string())?;
+    let result = external_diff.run(width);
+    resume(terminal).map_err(|e| e.to_string())?;
+    result.map_err(|e| format!("{:#?}", e))
+}
+
+fn handle_event(event: &KeyEvent, screen: &AppScreen, keys: &KeyBindings) -> Option<UiEvent> {
     if event.kind != KeyEventKind::Press {
         return None;
     }
 
     // Global bindings (work in all screens)
-    match (event.code, event.modifiers) {
-        (KeyCode::Char('c'), KeyModifiers::CONTROL) => {
-            return Some(UiEvent::Exit);
-        }
-        _ => {}
+    if let (KeyCode::Char('c'), KeyModifiers::CONTROL) = (event.code, event.modifiers) {
+        return Some(UiEvent::Exit);
     }
 
     // Screen-specific bindings
     match screen {
-        AppScreen::List(list_view) => handle_list_keys(event, list_view),
-        AppScreen::DiffView(_) => handle_diff_keys(event),
+        AppScreen::List(list_view) => handle_list_keys(event, list_view, keys),
+        AppScreen::DiffView(diff_view) => handle_diff_keys(event, diff_view, keys),
+        AppScreen::DiffStat(diffstat_view) => handle_diffstat_keys(event, diffstat_view, keys),
         _ => None,
     }
 }
 
-fn handle_list_keys(event: &KeyEvent, list_view: &crate::tui::state::ListView) -> Option<UiEvent> {
+fn handle_list_keys(
+    event: &KeyEvent,
+    list_view: &crate::tui::state::ListView,
+    keys: &KeyBindings,
+) -> Option<UiEvent> {
     match (event.code, event.modifiers) {
-        (KeyCode::Char('q'), _) => Some(UiEvent::Exit),
+        (KeyCode::Char(c), _) if c == keys.quit => Some(UiEvent::Exit),
         (KeyCode::Down | KeyCode::Char('j'), _) => {
             Some(UiEvent::Scroll(ScrollEvent::Single(ScrollDirection::Down)))
         }
@@ -103,7 +160,14 @@ fn handle_list_keys(event: &KeyEvent, list_view: &crate::tui::state::ListView) -
         (KeyCode::Enter | KeyCode::Char('l'), _) => {
             list_view.list_state.selected().map(UiEvent::EnterDiff)
         }
-        (KeyCode::Char('h'), _) => Some(UiEvent::ToggleUnchanged),
+        (KeyCode::Char(c), _) if c == keys.diffstat => {
+            list_view.list_state.selected().map(UiEvent::EnterDiffStat)
+        }
+        (KeyCode::Char(c), _) if c == keys.toggle_unchanged => Some(UiEvent::ToggleUnchanged),
+        (KeyCode::Char(c), _) if c == keys.toggle_trunk_merges => Some(UiEvent::ToggleTrunkMerges),
+        (KeyCode::Char(c), _) if c == keys.toggle_filter && list_view.filter_enabled.is_some() => {
+            Some(UiEvent::ToggleFilter)
+        }
         (KeyCode::Char('['), _) => {
             if list_view.base_index > 0 {
                 Some(UiEvent::PatchsetChange((
@@ -169,7 +233,32 @@ fn handle_list_keys(event: &KeyEvent, list_view: &crate::tui::state::ListView) -
     }
 }
 
-fn handle_diff_keys(event: &KeyEvent) -> Option<UiEvent> {
+fn handle_diffstat_keys(
+    event: &KeyEvent,
+    diffstat_view: &crate::tui::state::DiffStatView,
+    keys: &KeyBindings,
+) -> Option<UiEvent> {
+    match (event.code, event.modifiers) {
+        (KeyCode::Down | KeyCode::Char('j'), _) => {
+            Some(UiEvent::Scroll(ScrollEvent::Single(ScrollDirection::Down)))
+        }
+        (KeyCode::Up | KeyCode::Char('k'), _) => {
+            Some(UiEvent::Scroll(ScrollEvent::Single(ScrollDirection::Up)))
+        }
+        (KeyCode::Enter | KeyCode::Char('l'), _) => {
+            diffstat_view.list_state.selected().map(UiEvent::JumpToFile)
+        }
+        (KeyCode::Backspace | KeyCode::Left, _) => Some(UiEvent::BackToList),
+        (KeyCode::Char(c), _) if c == keys.quit => Some(UiEvent::BackToList),
+        _ => None,
+    }
+}
+
+fn handle_diff_keys(
+    event: &KeyEvent,
+    diff_view: &crate::tui::state::DiffView,
+    keys: &KeyBindings,
+) -> Option<UiEvent> {
     match (event.code, event.modifiers) {
         (KeyCode::Up | KeyCode::Char('k'), KeyModifiers::NONE) => {
             Some(UiEvent::Scroll(ScrollEvent::Single(ScrollDirection::Up)))
@@ -191,15 +280,36 @@ fn handle_diff_keys(event: &KeyEvent) -> Option<UiEvent> {
         }
         (KeyCode::Char('g'), _) => Some(UiEvent::Scroll(ScrollEvent::Top)),
         (KeyCode::Char('G'), _) => Some(UiEvent::Scroll(ScrollEvent::Bottom)),
-        (KeyCode::Backspace | KeyCode::Left | KeyCode::Char('q'), KeyModifiers::NONE) => {
-            Some(UiEvent::BackToList)
+        (KeyCode::Backspace | KeyCode::Left, KeyModifiers::NONE) => Some(UiEvent::BackToList),
+        (KeyCode::Char(c), KeyModifiers::NONE) if c == keys.quit => Some(UiEvent::BackToList),
+        (KeyCode::Char(c), KeyModifiers::NONE) if c == keys.copy => Some(UiEvent::CopyToClipboard),
+        (KeyCode::Char(c), KeyModifiers::NONE) if c == keys.toggle_upstream => {
+            Some(UiEvent::ToggleUpstream)
+        }
+        (KeyCode::Char(c), KeyModifiers::NONE) if c == keys.toggle_format => {
+            Some(UiEvent::ToggleFormat)
+        }
+        (KeyCode::Char(c), KeyModifiers::NONE) if c == keys.toggle_split => {
+            Some(UiEvent::ToggleSplit)
+        }
+        (KeyCode::Char(c), KeyModifiers::NONE) if c == keys.toggle_highlight => {
+            Some(UiEvent::ToggleHighlight)
+        }
+        (KeyCode::Char(c), KeyModifiers::NONE)
+            if c == keys.expand && (diff_view.expanded || !diff_view.collapsed.is_empty()) =>
+        {
+            Some(UiEvent::ExpandCollapsed)
+        }
+        (KeyCode::Char(c), KeyModifiers::NONE)
+            if c == keys.external_tool && diff_view.has_external_tool =>
+        {
+            Some(UiEvent::RunExternalTool)
         }
-        (KeyCode::Char('y'), KeyModifiers::NONE) => Some(UiEvent::CopyToClipboard),
         _ => None,
     }
 }
 
-fn draw(screen: &AppScreen, f: &mut ratatui::Frame) {
+fn draw(screen: &AppScreen, f: &mut ratatui::Frame, keys: &KeyBindings) {
     let chunks = Layout::default()
         .direction(Direction::Vertical)
         .constraints([
@@ -213,6 +323,7 @@ fn draw(screen: &AppScreen, f: &mut ratatui::Frame) {
     let header_text = match screen {
         AppScreen::Loading(_) => "diffsoup - Loading...".to_string(),
         AppScreen::Exit => "diffsoup - Exiting...".to_string(),
+        AppScreen::External(_) => "diffsoup - External diff tool".to_string(),
         AppScreen::Error(_) => "diffsoup - Error".to_string(),
         AppScreen::List(list_view) => {
             let total = list_view.total_commits;
@@ -226,13 +337,17 @@ fn draw(screen: &AppScreen, f: &mut ratatui::Frame) {
                 list_view.comparison_name
             )
         }
+        AppScreen::DiffView(diff_view) if !diff_view.conflicts.is_empty() => {
+            "diffsoup - Interdiff View (contains conflicts)".to_string()
+        }
         AppScreen::DiffView(_) => "diffsoup - Interdiff View".to_string(),
+        AppScreen::DiffStat(_) => "diffsoup - Diffstat".to_string(),
     };
 
     let header = Paragraph::new(header_text)
         .style(
             Style::default()
-                .fg(Color::Cyan)
+                .fg(theme().accent)
                 .add_modifier(Modifier::BOLD),
         )
         .block(Block::default().borders(Borders::ALL));
@@ -247,7 +362,7 @@ fn draw(screen: &AppScreen, f: &mut ratatui::Frame) {
                 render_message(f, chunks[1], "Loading...");
             };
         }
-        AppScreen::Exit => {}
+        AppScreen::Exit | AppScreen::External(_) => {}
         AppScreen::Error(Some(msg)) => {
             render_message(f, chunks[1], msg);
         }
@@ -260,23 +375,106 @@ fn draw(screen: &AppScreen, f: &mut ratatui::Frame) {
         AppScreen::DiffView(diff_view) => {
             render_interdiff(f, chunks[1], diff_view);
         }
+        AppScreen::DiffStat(diffstat_view) => {
+            render_diffstat(f, chunks[1], diffstat_view);
+        }
     }
 
     // Render footer
     let footer_text = match screen {
-        AppScreen::Loading(_) | AppScreen::Exit | AppScreen::Error(_) => "".to_string(),
+        AppScreen::Loading(_) | AppScreen::Exit | AppScreen::Error(_) | AppScreen::External(_) => {
+            "".to_string()
+        }
         AppScreen::List(list_view) => {
             let hide_text = if list_view.show_unchanged {
                 "hide"
             } else {
                 "show"
             };
+            let filter_text = match list_view.filter_enabled {
+                Some(true) => format!(" | {}: Disable filter", keys.toggle_filter),
+                Some(false) => format!(" | {}: Enable filter", keys.toggle_filter),
+                None => String::new(),
+            };
+            let merges_text = if list_view.hide_trunk_merges {
+                "show"
+            } else {
+                "hide"
+            };
+            format!(
+                "{}: Quit | ↑↓/jk: Navigate | Enter: View | {}: Diffstat | {}: {} unchanged | {}: {} trunk merges | []: Base | {{}}: Comp | <>: Both{}",
+                keys.quit,
+                keys.diffstat,
+                keys.toggle_unchanged,
+                hide_text,
+                keys.toggle_trunk_merges,
+                merges_text,
+                filter_text
+            )
+        }
+        AppScreen::DiffView(crate::tui::state::DiffView {
+            notice: Some(notice),
+            ..
+        }) => notice.clone(),
+        AppScreen::DiffView(diff_view) => {
+            let upstream_text = match (&diff_view.upstream_diff, diff_view.show_upstream) {
+                (Some(_), false) => format!(" | {}: Show upstream changes", keys.toggle_upstream),
+                (Some(_), true) => format!(" | {}: Show interdiff", keys.toggle_upstream),
+                (None, _) => String::new(),
+            };
+            let next_format = match diff_view.format {
+                RenderFormat::Git => "Word diff",
+                RenderFormat::ColorWords => "Range diff",
+                RenderFormat::RangeDiff if diff_view.has_external_tool => "External diff",
+                RenderFormat::RangeDiff | RenderFormat::External => "Line diff",
+            };
+            let format_text = format!("{}: {next_format}", keys.toggle_format);
+            let collapse_text = match (diff_view.expanded, diff_view.collapsed.len()) {
+                (true, _) => format!(" | {}: Collapse large files", keys.expand),
+                (false, 0) => String::new(),
+                (false, n) => format!(" | {}: Expand {n} collapsed", keys.expand),
+            };
+            let external_text = if diff_view.has_external_tool {
+                format!(" | {}: Open in diff tool", keys.external_tool)
+            } else {
+                String::new()
+            };
+            let split_text = match (diff_view.format, diff_view.split) {
+                (RenderFormat::Git, false) => format!(" | {}: Side by side", keys.toggle_split),
+                (RenderFormat::Git, true) => format!(" | {}: Unified", keys.toggle_split),
+                (
+                    RenderFormat::ColorWords | RenderFormat::RangeDiff | RenderFormat::External,
+                    _,
+                ) => String::new(),
+            };
+            let highlight_text = match (diff_view.format, diff_view.highlight) {
+                (RenderFormat::Git, false) => {
+                    format!(" | {}: Highlight syntax", keys.toggle_highlight)
+                }
+                (RenderFormat::Git, true) => format!(" | {}: Plain colors", keys.toggle_highlight),
+                (
+                    RenderFormat::ColorWords | RenderFormat::RangeDiff | RenderFormat::External,
+                    _,
+                ) => String::new(),
+            };
             format!(
-                "q: Quit | ↑↓/jk: Navigate | Enter: View | h: {} unchanged | []: Base | {{}}: Comp | <>: Both",
-                hide_text
+                "{}: Back | ↑↓: Scroll | {}: Copy diff to clipboard | {}{}{}{}{}{}",
+                keys.quit,
+                keys.copy,
+                format_text,
+                split_text,
+                highlight_text,
+                upstream_text,
+                collapse_text,
+                external_text
+            )
+        }
+        AppScreen::DiffStat(_) => {
+            format!(
+                "{}: Back | ↑↓/jk: Navigate | Enter: Jump to file",
+                keys.quit
             )
         }
-        AppScreen::DiffView(_) => "q: Back | ↑↓: Scroll | y: Copy diff to clipboard".to_string(),
     };
 
     let footer = Paragraph::new(footer_text)
@@ -300,7 +498,7 @@ fn render_list(
     let visible_commits = list_view.get_visible_commits();
 
     let title = format!(
-        "Commit Comparison: {} → {} ({}/{} commits{})",
+        "Commit Comparison: {} → {} ({}/{} commits{}{})",
         list_view.base_name,
         list_view.comparison_name,
         visible_commits.len(),
@@ -309,6 +507,11 @@ fn render_list(
             ""
         } else {
             ", changed only"
+        },
+        if list_view.filter_enabled == Some(true) {
+            ", filtered"
+        } else {
+            ""
         }
     );
 
@@ -320,7 +523,7 @@ fn render_list(
     let block = Block::default()
         .title(title)
         .borders(Borders::ALL)
-        .border_style(Style::default().fg(Color::Cyan));
+        .border_style(Style::default().fg(theme().accent));
 
     let list = List::new(items)
         .block(block)
@@ -331,23 +534,111 @@ fn render_list(
     f.render_stateful_widget(list, area, &mut list_state);
 }
 
+fn render_diffstat(
+    f: &mut ratatui::Frame,
+    area: ratatui::layout::Rect,
+    diffstat_view: &crate::tui::state::DiffStatView,
+) {
+    let stats = &diffstat_view.commit.stats;
+    let path_width = stats
+        .files
+        .iter()
+        .map(|file| file.path.chars().count())
+        .max()
+        .unwrap_or_default();
+    // Binary files are shown as `Bin` like in git, without a bar
+    let count = |file: &FileStats| {
+        if file.binary {
+            "Bin".to_string()
+        } else {
+            (file.additions + file.removals).to_string()
+        }
+    };
+    let count_width = stats
+        .files
+        .iter()
+        .map(|file| count(file).len())
+        .max()
+        .unwrap_or_default();
+    // Like git, the bar is scaled down only if the largest change doesn't fit
+    let bar_width = usize::from(area.width)
+        .saturating_sub(path_width + count_width + 8)
+        .max(10);
+    let max_changes = stats
+        .files
+        .iter()
+        .map(|file| file.additions + file.removals)
+        .max()
+        .unwrap_or_default();
+    let scale = |count: usize| {
+        if max_changes <= bar_width {
+            count
+        } else {
+            (count * bar_width).div_ceil(max_changes)
+        }
+    };
+
+    let items: Vec<ListItem> = stats
+        .files
+        .iter()
+        .map(|file| {
+            ListItem::new(Line::from(vec![
+                Span::raw(format!(
+                    " {:<path_width$} | {:>count_width$} ",
+                    file.path,
+                    count(file)
+                )),
+                Span::styled(
+                    "+".repeat(scale(file.additions)),
+                    Style::default().fg(theme().added),
+                ),
+                Span::styled(
+                    "-".repeat(scale(file.removals)),
+                    Style::default().fg(theme().removed),
+                ),
+            ]))
+        })
+        .collect();
+
+    let subject = diffstat_view
+        .commit
+        .to
+        .as_ref()
+        .or(diffstat_view.commit.from.as_ref())
+        .and_then(|meta| meta.message.lines().next())
+        .unwrap_or("<no message>");
+    let block = Block::default()
+        .title(format!("Diffstat: {subject}"))
+        .title_bottom(format!(
+            "{} files changed, {} insertions(+), {} deletions(-)",
+            stats.changed_files, stats.additions, stats.removals
+        ))
+        .borders(Borders::ALL)
+        .border_style(Style::default().fg(theme().accent));
+
+    let list = List::new(items)
+        .block(block)
+        .highlight_style(Style::default().reversed().add_modifier(Modifier::BOLD));
+
+    let mut list_state = diffstat_view.list_state.clone();
+    f.render_stateful_widget(list, area, &mut list_state);
+}
+
 fn format_commit_item(commit: &CommitDiff) -> ListItem<'_> {
     let has_changes = commit.has_changes();
 
-    let (status_icon, base_style) = match (&commit.from, &commit.to) {
-        (None, Some(_)) => ("+ ", Style::default().fg(Color::Green)),
-        (Some(_), None) => ("- ", Style::default().fg(Color::Red)),
-        (Some(from), Some(to)) if from.message != to.message => {
-            ("✎ ", Style::default().fg(Color::Cyan))
-        }
-        (Some(from), Some(to)) if from.sha != to.sha && has_changes => {
-            ("~ ", Style::default().fg(Color::Yellow))
+    let (status_icon, base_style) = match commit.status() {
+        CommitStatus::Added => ("+ ", Style::default().fg(theme().added)),
+        CommitStatus::Removed => ("- ", Style::default().fg(theme().removed)),
+        CommitStatus::Reworded => ("✎ ", Style::default().fg(theme().reworded)),
+        CommitStatus::Changed => ("~ ", Style::default().fg(theme().changed)),
+        CommitStatus::Rebased | CommitStatus::Unchanged => {
+            ("  ", Style::default().fg(theme().dimmed))
         }
-        _ => ("  ", Style::default().fg(Color::DarkGray)),
     };
 
     let style = if !has_changes {
-        Style::default().fg(Color::DarkGray)
+        Style::default().fg(theme().dimmed)
     } else {
         base_style
     };
@@ -379,11 +670,47 @@ fn format_commit_item(commit: &CommitDiff) -> ListItem<'_> {
         String::new()
     };
 
+    let conflicts_text = if commit.conflicts.is_empty() {
+        String::new()
+    } else {
+        format!(" ⚠ {} conflicted", commit.conflicts.len())
+    };
+
+    let metadata_text = match (&commit.from, &commit.to) {
+        (Some(from), Some(to)) => {
+            let fields: Vec<&str> = from
+                .metadata_changes(to)
+                .iter()
+                .map(|change| change.field)
+                .collect();
+            if fields.is_empty() {
+                String::new()
+            } else {
+                format!(" ⚑ {}", fields.join(", ").to_lowercase())
+            }
+        }
+        _ => String::new(),
+    };
+
+    let merge_text = if commit
+        .to
+        .as_ref()
+        .or(commit.from.as_ref())
+        .is_some_and(CommitMeta::is_merge)
+    {
+        " ⑂ merge"
+    } else {
+        ""
+    };
+
     let line = Line::from(vec![
         Span::styled(status_icon, style),
         Span::styled(format!("{:<16} ", sha_info), style),
         Span::styled(message, style),
-        Span::styled(stats_text, Style::default().fg(Color::DarkGray)),
+        Span::styled(merge_text, Style::default().fg(theme().merge)),
+        Span::styled(stats_text, Style::default().fg(theme().dimmed)),
+        Span::styled(conflicts_text, Style::default().fg(theme().conflict)),
+        Span::styled(metadata_text, Style::default().fg(theme().metadata)),
     ]);
 
     ListItem::new(line).style(style)
@@ -394,34 +721,277 @@ fn render_interdiff(
     area: ratatui::layout::Rect,
     diff_view: &crate::tui::state::DiffView,
 ) {
-    let lines: Vec<Line> = diff_view
-        .diff
-        .lines()
-        .map(|line| {
-            let style = if line.starts_with('+') && !line.starts_with("+++") {
-                Style::default().fg(Color::Green)
-            } else if line.starts_with('-') && !line.starts_with("---") {
-                Style::default().fg(Color::Red)
-            } else if line.starts_with("@@") {
-                Style::default().fg(Color::Cyan)
-            } else if line.starts_with("diff") || line.starts_with("index") {
-                Style::default().fg(Color::Yellow)
-            } else {
-                Style::default()
-            };
-            Line::from(Span::styled(line.to_string(), style))
-        })
-        .collect();
-
-    let length: u16 = lines.len().try_into().unwrap_or(u16::MAX);
+    let length: u16 = diff_view.line_count().try_into().unwrap_or(u16::MAX);
     let scroll = diff_view.scroll.min(length);
 
+    let conflicts_text = match diff_view.conflicts.len() {
+        0 => String::new(),
+        1 => " (1 conflicted file)".to_string(),
+        n => format!(" ({n} conflicted files)"),
+    };
     let block = Block::default()
-        .title_top(format!("Interdiff View: {}", diff_view.commit))
+        .title_top(if diff_view.show_upstream {
+            format!("Upstream Changes: {}", diff_view.commit)
+        } else {
+            format!("Interdiff View: {}{}", diff_view.commit, conflicts_text)
+        })
         .title_bottom(format!("{} / {}", scroll, length))
         .borders(Borders::ALL);
 
+    if diff_view.is_split() {
+        let columns = Layout::default()
+            .direction(Direction::Horizontal)
+            .constraints([Constraint::Percentage(50), Constraint::Percentage(50)])
+            .split(block.inner(area));
+        f.render_widget(block, area);
+
+        let (mut left, mut right) = (Vec::new(), Vec::new());
+        for (title, section) in diff_view.header_sections() {
+            let (section_left, section_right) =
+                split_rows(patch::side_by_side_hunk(section), Some(title), &|line| {
+                    Line::styled(line, diff_line_style(line))
+                });
+            left.extend(section_left);
+            right.extend(section_right);
+        }
+        // The rows only borrow the lines, their index is recovered from the offset in the text
+        let text = diff_view.text();
+        let line_starts: Vec<usize> = text
+            .lines()
+            .map(|line| line.as_ptr() as usize - text.as_ptr() as usize)
+            .collect();
+        let conflicted_lines = conflicted_lines(diff_view);
+        let (diff_left, diff_right) = split_rows(patch::side_by_side(text), None, &|line| {
+            let offset = line.as_ptr() as usize - text.as_ptr() as usize;
+            let index = line_starts
+                .partition_point(|start| *start <= offset)
+                .saturating_sub(1);
+            git_diff_line(diff_view, &conflicted_lines, index, line)
+        });
+        left.extend(diff_left);
+        right.extend(diff_right);
+        let left = Paragraph::new(left)
+            .block(Block::default().borders(Borders::RIGHT))
+            .scroll((scroll, 0));
+        f.render_widget(left, columns[0]);
+        f.render_widget(Paragraph::new(right).scroll((scroll, 0)), columns[1]);
+        return;
+    }
+
+    let mut lines: Vec<Line> = diff_view
+        .header_sections()
+        .into_iter()
+        .flat_map(|(title, section)| section_lines(title, section))
+        .collect();
+    lines.extend(unified_lines(diff_view));
     let content = Paragraph::new(lines).block(block).scroll((scroll, 0));
 
     f.render_widget(content, area);
 }
+
+fn unified_lines(diff_view: &crate::tui::state::DiffView) -> Vec<Line<'_>> {
+    if matches!(
+        diff_view.format,
+        RenderFormat::ColorWords | RenderFormat::External
+    ) {
+        diff_view
+            .text()
+            .lines()
+            .scan(Style::default(), |style, line| {
+                let (line, next) = ansi::parse_line(line, *style);
+                *style = next;
+                Some(line)
+            })
+            .collect()
+    } else if diff_view.format == RenderFormat::RangeDiff {
+        diff_view
+            .text()
+            .lines()
+            .map(|line| Line::from(range_diff_line_spans(line)))
+            .collect()
+    } else {
+        let conflicted_lines = conflicted_lines(diff_view);
+        diff_view
+            .text()
+            .lines()
+            .enumerate()
+            .map(|(index, line)| git_diff_line(diff_view, &conflicted_lines, index, line))
+            .collect()
+    }
+}
+
+/// Line ranges of the shown git diff belonging to conflicted files.
+fn conflicted_lines(diff_view: &crate::tui::state::DiffView) -> Vec<std::ops::Range<usize>> {
+    if diff_view.show_upstream || diff_view.conflicts.is_empty() {
+        Vec::new()
+    } else {
+        conflicted_ranges(&diff_view.diff, &diff_view.conflicts)
+    }
+}
+
+/// Styles the line with the given index of the shown git diff, marking conflicts and upstream
+/// hunks before falling back to syntax highlighting and the plain diff colors.
+fn git_diff_line<'a>(
+    diff_view: &crate::tui::state::DiffView,
+    conflicted_lines: &[std::ops::Range<usize>],
+    index: usize,
+    line: &'a str,
+) -> Line<'a> {
+    let conflict_style = Style::default().fg(theme().conflict);
+    if conflicted_lines.iter().any(|range| range.contains(&index)) {
+        if line.starts_with("diff") {
+            return Line::from(vec![
+                Span::styled(line, conflict_style.add_modifier(Modifier::BOLD)),
+                Span::styled(
+                    " (conflicted, interdiff is approximate)",
+                    conflict_style.add_modifier(Modifier::ITALIC),
+                ),
+            ]);
+        }
+        if is_conflict_marker(line) {
+            return Line::from(Span::styled(
+                line,
+                conflict_style.add_modifier(Modifier::BOLD),
+            ));
+        }
+    }
+    if !diff_view.show_upstream && diff_view.upstream_lines.contains(&index) {
+        let style = Style::default().fg(theme().dimmed);
+        return if line.starts_with("@@") {
+            Line::from(vec![
+                Span::styled(line, style),
+                Span::styled(" (upstream)", style.add_modifier(Modifier::ITALIC)),
+            ])
+        } else {
+            Line::from(Span::styled(line, style))
+        };
+    }
+    diff_view
+        .highlighted(line)
+        .unwrap_or_else(|| Line::from(Span::styled(line, diff_line_style(line))))
+}
+
+fn section_title(title: &'static str) -> Line<'static> {
+    Line::styled(
+        title,
+        Style::default()
+            .fg(theme().accent)
+            .add_modifier(Modifier::BOLD),
+    )
+}
+
+fn section_lines<'a>(title: &'static str, section: &'a str) -> Vec<Line<'a>> {
+    let mut lines = vec![section_title(title)];
+    lines.extend(
+        section
+            .lines()
+            .map(|line| Line::styled(line, diff_line_style(line))),
+    );
+    lines.push(Line::default());
+    lines
+}
+
+/// Converts side by side rows into the old and new column, optionally as a titled header
+/// section. `style` renders a single line.
+fn split_rows<'a>(
+    rows: Vec<SideBySideRow<'a>>,
+    title: Option<&'static str>,
+    style: &dyn Fn(&'a str) -> Line<'a>,
+) -> (Vec<Line<'a>>, Vec<Line<'a>>) {
+    let (mut left, mut right): (Vec<Line>, Vec<Line>) = rows
+        .into_iter()
+        .map(|row| match row {
+            SideBySideRow::Header(line) => {
+                let line = style(line);
+                (line.clone(), line)
+            }
+            SideBySideRow::Lines { left, right } => (
+                left.map(style).unwrap_or_default(),
+                right.map(style).unwrap_or_default(),
+            ),
+        })
+        .unzip();
+    if let Some(title) = title {
+        left.insert(0, section_title(title));
+        right.insert(0, section_title(title));
+        left.push(Line::default());
+        right.push(Line::default());
+    }
+    (left, right)
+}
+
+/// Colors the outer marker of a range-diff line by whether the patch line was dropped or
+/// introduced, and the rest by the inner marker.
+fn range_diff_line_spans(line: &str) -> Vec<Span<'_>> {
+    let Some(content) = line.strip_prefix("    ") else {
+        return vec![Span::styled(
+            line,
+            Style::default()
+                .fg(theme().file_header)
+                .add_modifier(Modifier::BOLD),
+        )];
+    };
+    let Some(outer) = content.chars().next() else {
+        return Vec::new();
+    };
+    let outer_style = match outer {
+        '+' => Style::default()
+            .fg(theme().added)
+            .add_modifier(Modifier::BOLD),
+        '-' => Style::default()
+            .fg(theme().removed)
+            .add_modifier(Modifier::BOLD),
+        _ => Style::default(),
+    };
+    let inner = &content[1..];
+    let inner_style = if outer == ' ' {
+        diff_line_style(inner).add_modifier(Modifier::DIM)
+    } else {
+        diff_line_style(inner)
+    };
+    vec![
+        Span::raw("    "),
+        Span::styled(&content[..1], outer_style),
+        Span::styled(inner, inner_style),
+    ]
+}
+
+fn diff_line_style(line: &str) -> Style {
+    if line.starts_with('+') && !line.starts_with("+++") {
+        Style::default().fg(theme().added)
+    } else if line.starts_with('-') && !line.starts_with("---") {
+        Style::default().fg(theme().removed)
+    } else if line.starts_with("@@") {
+        Style::default().fg(theme().hunk)
+    } else if line.starts_with("diff") || line.starts_with("index") {
+        Style::default().fg(theme().file_header)
+    } else {
+        Style::default()
+    }
+}
+
+/// Line ranges of the file sections in `diff` belonging to conflicted paths.
+fn conflicted_ranges(diff: &str, conflicts: &[String]) -> Vec<std::ops::Range<usize>> {
+    let files = patch::parse(diff);
+    files
+        .iter()
+        .enumerate()
+        .filter(|(_, file)| conflicts.iter().any(|path| path == file.path))
+        .map(|(i, file)| {
+            let end = files
+                .get(i + 1)
+                .map(|next| next.start)
+                .unwrap_or(usize::MAX);
+            file.start..end
+        })
+        .collect()
+}
+
+fn is_conflict_marker(line: &str) -> bool {
+    let content = line.get(1..).unwrap_or_default();
+    [
+        "<<<<<<<", ">>>>>>>", "|||||||", "=======", "%%%%%%%", "+++++++", "-------",
+    ]
+    .iter()
+    .any(|marker| content.starts_with(marker))
+}
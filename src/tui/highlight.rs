@@ -0,0 +1,149 @@
+use std::{collections::HashMap, path::Path, sync::LazyLock};
+
+use ratatui::{
+    style::{Color, Modifier, Style},
+    text::{Line, Span},
+};
+use syntect::{
+    easy::HighlightLines,
+    highlighting::{FontStyle, Theme, ThemeSet},
+    parsing::{SyntaxReference, SyntaxSet},
+};
+
+use crate::tui::settings::theme;
+
+static SYNTAXES: LazyLock<SyntaxSet> = LazyLock::new(two_face::syntax::extra_newlines);
+static THEMES: LazyLock<ThemeSet> = LazyLock::new(ThemeSet::load_defaults);
+
+/// Names of the themes that can be used for syntax highlighting.
+pub fn theme_names() -> Vec<&'static str> {
+    THEMES.themes.keys().map(String::as_str).collect()
+}
+
+/// Looks up one of syntect's default themes by name.
+pub fn load_theme(name: &str) -> Option<Theme> {
+    THEMES.themes.get(name).cloned()
+}
+
+/// Syntax highlighted hunk lines of a git diff.
+#[derive(Debug, Clone, Default)]
+pub struct Highlights {
+    /// Lines keyed by their byte offset within the diff
+    lines: HashMap<usize, Line<'static>>,
+}
+
+impl Highlights {
+    /// Returns the highlighted version of `line`, which has to be a slice of `diff`.
+    pub fn get(&self, diff: &str, line: &str) -> Option<&Line<'static>> {
+        let offset = (line.as_ptr() as usize).checked_sub(diff.as_ptr() as usize)?;
+        self.lines.get(&offset)
+    }
+}
+
+/// Highlights the code in all hunks of a git diff, based on the language of each file's path.
+///
+/// Removed lines are highlighted in the context of the old file and added lines in the context
+/// of the new one, with the add/remove background on top.
+pub fn highlight_diff(diff: &str, syntax_theme: &Theme) -> Highlights {
+    let mut highlights = Highlights::default();
+    let mut syntax: Option<&SyntaxReference> = None;
+    let mut state: Option<(HighlightLines, HighlightLines)> = None;
+    let mut offset = 0;
+
+    for raw_line in diff.split_inclusive('\n') {
+        let line_offset = offset;
+        offset += raw_line.len();
+        let line = raw_line.trim_end_matches(['\n', '\r']);
+
+        if let Some(paths) = line.strip_prefix("diff --git ") {
+            let path = paths
+                .rsplit_once(" b/")
+                .map(|(_, path)| path)
+                .unwrap_or(paths);
+            syntax = find_syntax(path);
+            state = None;
+            continue;
+        }
+        if line.starts_with("@@") {
+            // Hunks are highlighted independently since the lines between them are unknown
+            state = syntax.map(|syntax| {
+                (
+                    HighlightLines::new(syntax, syntax_theme),
+                    HighlightLines::new(syntax, syntax_theme),
+                )
+            });
+            continue;
+        }
+        let Some((old, new)) = state.as_mut() else {
+            continue;
+        };
+
+        let highlighted = match line.split_at_checked(1) {
+            Some(("+", code)) => highlight_line(new, "+", code, Some(theme().added_background)),
+            Some(("-", code)) => highlight_line(old, "-", code, Some(theme().removed_background)),
+            Some((" ", code)) => {
+                // Context lines exist on both sides and have to advance both parsers
+                let _ = highlight_line(old, " ", code, None);
+                highlight_line(new, " ", code, None)
+            }
+            _ => None,
+        };
+        if let Some(highlighted) = highlighted {
+            highlights.lines.insert(line_offset, highlighted);
+        }
+    }
+    highlights
+}
+
+fn find_syntax(path: &str) -> Option<&'static SyntaxReference> {
+    let path = Path::new(path);
+    let extension = path.extension().and_then(|extension| extension.to_str());
+    let file_name = path.file_name().and_then(|name| name.to_str());
+    extension
+        .and_then(|extension| SYNTAXES.find_syntax_by_extension(extension))
+        .or_else(|| file_name.and_then(|name| SYNTAXES.find_syntax_by_extension(name)))
+}
+
+fn highlight_line(
+    highlighter: &mut HighlightLines,
+    prefix: &'static str,
+    code: &str,
+    background: Option<Color>,
+) -> Option<Line<'static>> {
+    let code = format!("{code}\n");
+    let ranges = highlighter.highlight_line(&code, &SYNTAXES).ok()?;
+
+    let prefix_style = match prefix {
+        "+" => Style::default().fg(theme().added),
+        "-" => Style::default().fg(theme().removed),
+        _ => Style::default(),
+    };
+    let with_background = |style: Style| match background {
+        Some(background) => style.bg(background),
+        None => style,
+    };
+
+    let mut spans = vec![Span::styled(prefix, with_background(prefix_style))];
+    for (style, text) in ranges {
+        let text = text.trim_end_matches('\n');
+        if text.is_empty() {
+            continue;
+        }
+        let mut span_style = Style::default().fg(Color::Rgb(
+            style.foreground.r,
+            style.foreground.g,
+            style.foreground.b,
+        ));
+        if style.font_style.contains(FontStyle::BOLD) {
+            span_style = span_style.add_modifier(Modifier::BOLD);
+        }
+        if style.font_style.contains(FontStyle::ITALIC) {
+            span_style = span_style.add_modifier(Modifier::ITALIC);
+        }
+        if style.font_style.contains(FontStyle::UNDERLINE) {
+            span_style = span_style.add_modifier(Modifier::UNDERLINED);
+        }
+        spans.push(Span::styled(text.to_string(), with_background(span_style)));
+    }
+    Some(Line::from(spans).style(with_background(Style::default())))
+}
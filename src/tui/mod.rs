@@ -3,19 +3,31 @@ use std::sync::{
     mpsc::{self, SendError, Sender},
 };
 
-use diffsoup::pr::PrFetcher;
+use diffsoup::{
+    diff::{DiffOptions, RenderFormat},
+    pr::PrFetcher,
+};
 use jj_lib::{repo::ReadonlyRepo, workspace::Workspace};
+use ratatui::widgets::ListState;
 
-use crate::tui::{
-    app::spawn_ui_thread,
-    state::{AppScreen, AppState, UiEvent},
-    worker::{WorkerMsg, WorkerRequest, WorkerResponse, spawn_worker_thread},
+use crate::{
+    ansi,
+    tui::{
+        app::spawn_ui_thread,
+        settings::set_theme,
+        state::{AppScreen, AppState, DiffStatView, UiEvent},
+        worker::{WorkerMsg, WorkerRequest, WorkerResponse, spawn_worker_thread},
+    },
 };
 
 mod app;
+mod highlight;
+mod settings;
 mod state;
 mod worker;
 
+pub use settings::UiSettings;
+
 #[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
 pub struct JobId(u64);
 
@@ -51,20 +63,32 @@ pub fn run(
     workspace: Workspace,
     repo: Arc<ReadonlyRepo>,
     pr_fetcher: Box<dyn PrFetcher>,
+    options: DiffOptions,
+    settings: UiSettings,
 ) -> anyhow::Result<()> {
     let (view_tx, view_rx) = mpsc::channel();
     let (worker_request_tx, worker_request_rx) = mpsc::channel();
     let (main_tx, main_rx) = mpsc::channel();
 
-    let mut app = AppState::new(worker_request_tx);
+    let mut app = AppState::new(
+        worker_request_tx,
+        options.filter.is_some(),
+        options.format,
+        options.external_tool.is_some(),
+        options.hide_trunk_merges,
+        settings.show_unchanged,
+    );
 
-    let ui_handle = spawn_ui_thread(UiSender(main_tx.clone()), view_rx);
+    set_theme(settings.theme);
+    let ui_handle = spawn_ui_thread(UiSender(main_tx.clone()), view_rx, settings.keys);
     let worker_handle = spawn_worker_thread(
         WorkerSender(main_tx),
         worker_request_rx,
         workspace,
         repo,
         pr_fetcher,
+        options,
+        settings.syntax_theme,
     );
 
     let job_id = app.next_job();
@@ -85,106 +109,353 @@ pub fn run(
             }
             // discard if event is outdated
             MainThreadMsg::Worker(_) => {}
-            MainThreadMsg::Ui(action) => match action {
-                UiEvent::SizeChange(size) => {
-                    app.screen_size = size;
+            MainThreadMsg::Ui(action) => {
+                // Notices only last until the next key press
+                if let AppScreen::DiffView(diff_view) = &mut app.screen {
+                    diff_view.notice = None;
                 }
-                UiEvent::Exit => {
-                    app.screen = state::AppScreen::Exit;
-                    exit = true;
-                }
-                UiEvent::Scroll(event) => match &mut app.screen {
-                    AppScreen::List(list_view) => {
-                        let current = list_view.list_state.selected().unwrap_or_default();
-                        let new = event.get_new_index(
-                            app.screen_size,
-                            current,
-                            list_view.get_visible_commits().len(),
-                        );
-                        app.list_state.select(Some(new));
-                        list_view.list_state.select(Some(new));
-                    }
-                    AppScreen::DiffView(diff_view) => {
-                        diff_view.scroll = event
-                            .get_new_index(
+                match action {
+                    UiEvent::SizeChange(size) => {
+                        app.screen_size = size;
+                    }
+                    UiEvent::Exit => {
+                        app.screen = state::AppScreen::Exit;
+                        exit = true;
+                    }
+                    UiEvent::Scroll(event) => match &mut app.screen {
+                        AppScreen::List(list_view) => {
+                            let current = list_view.list_state.selected().unwrap_or_default();
+                            let new = event.get_new_index(
                                 app.screen_size,
-                                diff_view.scroll.into(),
-                                diff_view.diff.lines().count(),
+                                current,
+                                list_view.get_visible_commits().len(),
+                            );
+                            app.list_state.select(Some(new));
+                            list_view.list_state.select(Some(new));
+                        }
+                        AppScreen::DiffStat(diffstat_view) => {
+                            let current = diffstat_view.list_state.selected().unwrap_or_default();
+                            let new = event.get_new_index(
+                                app.screen_size,
+                                current,
+                                diffstat_view.commit.stats.files.len(),
+                            );
+                            diffstat_view.list_state.select(Some(new));
+                        }
+                        AppScreen::DiffView(diff_view) => {
+                            diff_view.scroll = event
+                                .get_new_index(
+                                    app.screen_size,
+                                    diff_view.scroll.into(),
+                                    diff_view.line_count(),
+                                )
+                                .try_into()
+                                .unwrap_or_default();
+                        }
+                        _ => {}
+                    },
+                    UiEvent::PatchsetChange((from_index, to_index)) => {
+                        if let (Some(from), Some(to)) = (
+                            app.commit_list.get(from_index),
+                            app.commit_list.get(to_index),
+                        ) {
+                            let job_id = app.next_job();
+                            app.worker_req_tx.send(WorkerMsg {
+                                job_id,
+                                msg: WorkerRequest::CalculateBranchDiff {
+                                    from_index,
+                                    to_index,
+                                    from: from.into(),
+                                    to: to.into(),
+                                    apply_filter: app.apply_filter(),
+                                    hide_trunk_merges: app.hide_trunk_merges,
+                                },
+                            })?;
+                            app.current_job = Some(job_id);
+                        }
+                    }
+                    UiEvent::EnterDiff(usize) => {
+                        if let AppScreen::List(ref list_view) = app.screen
+                            && let Some(entry) = list_view.get_visible_commits().get(usize)
+                        {
+                            let job_id = app.next_job();
+                            app.worker_req_tx.send(WorkerMsg {
+                                job_id,
+                                msg: WorkerRequest::RenderInterdiff {
+                                    from: entry.from.as_ref().map(|e| e.sha.clone()),
+                                    to: entry.to.as_ref().map(|e| e.sha.clone()),
+                                    render_width: app.render_width(app.diff_format),
+                                    scroll: 0,
+                                    apply_filter: app.apply_filter(),
+                                    format: app.diff_format,
+                                    expand: false,
+                                    highlight: app.syntax_highlight,
+                                    jump_to: None,
+                                },
+                            })?;
+                            app.current_job = Some(job_id);
+                        }
+                    }
+                    UiEvent::EnterDiffStat(index) => {
+                        if let AppScreen::List(ref list_view) = app.screen
+                            && let Some(entry) = list_view.get_visible_commits().get(index)
+                            && !entry.stats.files.is_empty()
+                        {
+                            app.screen = AppScreen::DiffStat(DiffStatView {
+                                commit: (*entry).clone(),
+                                list_state: ListState::default().with_selected(Some(0)),
+                            });
+                        }
+                    }
+                    UiEvent::JumpToFile(index) => {
+                        if let AppScreen::DiffStat(ref diffstat_view) = app.screen
+                            && let Some(file) = diffstat_view.commit.stats.files.get(index)
+                        {
+                            let entry = &diffstat_view.commit;
+                            let job_id = app.next_job();
+                            app.worker_req_tx.send(WorkerMsg {
+                                job_id,
+                                msg: WorkerRequest::RenderInterdiff {
+                                    from: entry.from.as_ref().map(|e| e.sha.clone()),
+                                    to: entry.to.as_ref().map(|e| e.sha.clone()),
+                                    render_width: app.render_width(app.diff_format),
+                                    scroll: 0,
+                                    apply_filter: app.apply_filter(),
+                                    format: app.diff_format,
+                                    expand: false,
+                                    highlight: app.syntax_highlight,
+                                    jump_to: Some(file.path.clone()),
+                                },
+                            })?;
+                            app.current_job = Some(job_id);
+                        }
+                    }
+                    UiEvent::BackToList => {
+                        if let (Some(from), Some(to)) = (
+                            app.commit_list.get(app.base_index),
+                            app.commit_list.get(app.comparison_index),
+                        ) {
+                            let job_id = app.next_job();
+                            app.worker_req_tx.send(WorkerMsg {
+                                job_id,
+                                msg: WorkerRequest::CalculateBranchDiff {
+                                    from_index: app.base_index,
+                                    to_index: app.comparison_index,
+                                    from: from.into(),
+                                    to: to.into(),
+                                    apply_filter: app.apply_filter(),
+                                    hide_trunk_merges: app.hide_trunk_merges,
+                                },
+                            })?;
+                            app.current_job = Some(job_id);
+                        }
+                    }
+                    UiEvent::ToggleUnchanged => {
+                        if let AppScreen::List(list_view) = &mut app.screen {
+                            app.show_unchanged = !app.show_unchanged;
+                            list_view.show_unchanged = app.show_unchanged;
+                            list_view.list_state.select(Some(0));
+                            app.list_state.select(Some(0));
+                        }
+                    }
+                    UiEvent::ToggleFilter => {
+                        if let (AppScreen::List(_), Some(enabled)) =
+                            (&app.screen, app.filter_enabled)
+                            && let (Some(from), Some(to)) = (
+                                app.commit_list.get(app.base_index),
+                                app.commit_list.get(app.comparison_index),
                             )
-                            .try_into()
-                            .unwrap_or_default();
-                    }
-                    _ => {}
-                },
-                UiEvent::PatchsetChange((from_index, to_index)) => {
-                    if let (Some(from), Some(to)) = (
-                        app.commit_list.get(from_index),
-                        app.commit_list.get(to_index),
-                    ) {
-                        let job_id = app.next_job();
-                        app.worker_req_tx.send(WorkerMsg {
-                            job_id,
-                            msg: WorkerRequest::CalculateBranchDiff {
-                                from_index,
-                                to_index,
-                                from: from.into(),
-                                to: to.into(),
-                            },
-                        })?;
-                        app.current_job = Some(job_id);
+                        {
+                            app.filter_enabled = Some(!enabled);
+                            let job_id = app.next_job();
+                            app.worker_req_tx.send(WorkerMsg {
+                                job_id,
+                                msg: WorkerRequest::CalculateBranchDiff {
+                                    from_index: app.base_index,
+                                    to_index: app.comparison_index,
+                                    from: from.into(),
+                                    to: to.into(),
+                                    apply_filter: app.apply_filter(),
+                                    hide_trunk_merges: app.hide_trunk_merges,
+                                },
+                            })?;
+                            app.current_job = Some(job_id);
+                        }
                     }
-                }
-                UiEvent::EnterDiff(usize) => {
-                    if let AppScreen::List(ref list_view) = app.screen
-                        && let Some(entry) = list_view.get_visible_commits().get(usize)
-                    {
-                        let job_id = app.next_job();
-                        app.worker_req_tx.send(WorkerMsg {
-                            job_id,
-                            msg: WorkerRequest::RenderInterdiff {
-                                from: entry.from.as_ref().map(|e| e.sha.clone()),
-                                to: entry.to.as_ref().map(|e| e.sha.clone()),
-                                render_width: app.screen_size.0,
-                                scroll: 0,
-                            },
-                        })?;
-                        app.current_job = Some(job_id);
+                    UiEvent::ToggleTrunkMerges => {
+                        if let (AppScreen::List(_), Some(from), Some(to)) = (
+                            &app.screen,
+                            app.commit_list.get(app.base_index),
+                            app.commit_list.get(app.comparison_index),
+                        ) {
+                            app.hide_trunk_merges = !app.hide_trunk_merges;
+                            let job_id = app.next_job();
+                            app.worker_req_tx.send(WorkerMsg {
+                                job_id,
+                                msg: WorkerRequest::CalculateBranchDiff {
+                                    from_index: app.base_index,
+                                    to_index: app.comparison_index,
+                                    from: from.into(),
+                                    to: to.into(),
+                                    apply_filter: app.apply_filter(),
+                                    hide_trunk_merges: app.hide_trunk_merges,
+                                },
+                            })?;
+                            app.current_job = Some(job_id);
+                        }
                     }
-                }
-                UiEvent::BackToList => {
-                    if let (Some(from), Some(to)) = (
-                        app.commit_list.get(app.base_index),
-                        app.commit_list.get(app.comparison_index),
-                    ) {
-                        let job_id = app.next_job();
-                        app.worker_req_tx.send(WorkerMsg {
-                            job_id,
-                            msg: WorkerRequest::CalculateBranchDiff {
-                                from_index: app.base_index,
-                                to_index: app.comparison_index,
-                                from: from.into(),
-                                to: to.into(),
-                            },
-                        })?;
-                        app.current_job = Some(job_id);
+                    UiEvent::ToggleUpstream => {
+                        if let AppScreen::DiffView(diff_view) = &mut app.screen {
+                            if diff_view.upstream_diff.is_some() {
+                                diff_view.show_upstream = !diff_view.show_upstream;
+                                diff_view.scroll = 0;
+                            } else {
+                                diff_view.notice = Some(
+                                    "No upstream changes to the files of this interdiff".into(),
+                                );
+                            }
+                        }
                     }
-                }
-                UiEvent::ToggleUnchanged => {
-                    if let AppScreen::List(list_view) = &mut app.screen {
-                        app.show_unchanged = !app.show_unchanged;
-                        list_view.show_unchanged = app.show_unchanged;
-                        list_view.list_state.select(Some(0));
-                        app.list_state.select(Some(0));
+                    UiEvent::ToggleHighlight => {
+                        if let AppScreen::DiffView(diff_view) = &mut app.screen {
+                            app.syntax_highlight = !diff_view.highlight;
+                            diff_view.highlight = app.syntax_highlight;
+                        }
+                        // Highlighting is done by the worker, so the first time it is turned on
+                        // the diff has to be rendered again
+                        if let AppScreen::DiffView(diff_view) = &app.screen
+                            && diff_view.highlight
+                            && diff_view.format == RenderFormat::Git
+                            && diff_view.highlights.is_none()
+                        {
+                            let job_id = app.next_job();
+                            app.worker_req_tx.send(WorkerMsg {
+                                job_id,
+                                msg: WorkerRequest::RenderInterdiff {
+                                    from: diff_view.from.clone(),
+                                    to: diff_view.to.clone(),
+                                    render_width: app.render_width(diff_view.format),
+                                    scroll: diff_view.scroll,
+                                    apply_filter: app.apply_filter(),
+                                    format: diff_view.format,
+                                    expand: diff_view.expanded,
+                                    highlight: true,
+                                    jump_to: None,
+                                },
+                            })?;
+                            app.current_job = Some(job_id);
+                        }
                     }
-                }
-                UiEvent::CopyToClipboard => {
-                    if let (AppScreen::DiffView(diff_view), Ok(mut clipboard)) =
-                        (&app.screen, arboard::Clipboard::new())
-                    {
-                        clipboard.set_text(&diff_view.diff).ok();
+                    UiEvent::ToggleSplit => {
+                        match &mut app.screen {
+                            AppScreen::DiffView(diff_view)
+                                if diff_view.format != RenderFormat::Git =>
+                            {
+                                diff_view.notice = Some(format!(
+                                    "Side by side view not supported in {} diffs",
+                                    diff_view.format
+                                ));
+                            }
+                            AppScreen::DiffView(diff_view) => {
+                                app.split_view = !diff_view.split;
+                                diff_view.split = app.split_view;
+                                diff_view.scroll = 0;
+                            }
+                            _ => {}
+                        }
+                        // The panes are half as wide as the unified view
+                        if let AppScreen::DiffView(diff_view) = &app.screen
+                            && diff_view.format == RenderFormat::Git
+                        {
+                            let job_id = app.next_job();
+                            app.worker_req_tx.send(WorkerMsg {
+                                job_id,
+                                msg: WorkerRequest::RenderInterdiff {
+                                    from: diff_view.from.clone(),
+                                    to: diff_view.to.clone(),
+                                    render_width: app.render_width(diff_view.format),
+                                    scroll: 0,
+                                    apply_filter: app.apply_filter(),
+                                    format: diff_view.format,
+                                    expand: diff_view.expanded,
+                                    highlight: app.syntax_highlight,
+                                    jump_to: None,
+                                },
+                            })?;
+                            app.current_job = Some(job_id);
+                        }
+                    }
+                    UiEvent::ToggleFormat => {
+                        if let AppScreen::DiffView(diff_view) = &app.screen {
+                            app.diff_format = diff_view.format.toggle(app.has_external_tool);
+                            let job_id = app.next_job();
+                            app.worker_req_tx.send(WorkerMsg {
+                                job_id,
+                                msg: WorkerRequest::RenderInterdiff {
+                                    from: diff_view.from.clone(),
+                                    to: diff_view.to.clone(),
+                                    render_width: app.render_width(app.diff_format),
+                                    scroll: diff_view.scroll,
+                                    apply_filter: app.apply_filter(),
+                                    format: app.diff_format,
+                                    expand: diff_view.expanded,
+                                    highlight: app.syntax_highlight,
+                                    jump_to: None,
+                                },
+                            })?;
+                            app.current_job = Some(job_id);
+                        }
+                    }
+                    UiEvent::ExpandCollapsed => {
+                        if let AppScreen::DiffView(diff_view) = &app.screen {
+                            let job_id = app.next_job();
+                            app.worker_req_tx.send(WorkerMsg {
+                                job_id,
+                                msg: WorkerRequest::RenderInterdiff {
+                                    from: diff_view.from.clone(),
+                                    to: diff_view.to.clone(),
+                                    render_width: app.render_width(diff_view.format),
+                                    scroll: diff_view.scroll,
+                                    apply_filter: app.apply_filter(),
+                                    format: diff_view.format,
+                                    expand: !diff_view.expanded,
+                                    highlight: app.syntax_highlight,
+                                    jump_to: None,
+                                },
+                            })?;
+                            app.current_job = Some(job_id);
+                        }
+                    }
+                    UiEvent::RunExternalTool => {
+                        if let AppScreen::DiffView(diff_view) = &app.screen {
+                            let job_id = app.next_job();
+                            app.worker_req_tx.send(WorkerMsg {
+                                job_id,
+                                msg: WorkerRequest::PrepareExternalDiff {
+                                    from: diff_view.from.clone(),
+                                    to: diff_view.to.clone(),
+                                    apply_filter: app.apply_filter(),
+                                },
+                            })?;
+                            app.current_job = Some(job_id);
+                        }
+                    }
+                    UiEvent::ExternalToolExited(error) => {
+                        app.screen = match (error, app.suspended_view.take()) {
+                            (Some(error), _) => AppScreen::Error(Some(error)),
+                            (None, Some(diff_view)) => AppScreen::DiffView(diff_view),
+                            (None, None) => AppScreen::Error(None),
+                        };
+                    }
+                    UiEvent::CopyToClipboard => {
+                        if let (AppScreen::DiffView(diff_view), Ok(mut clipboard)) =
+                            (&app.screen, arboard::Clipboard::new())
+                        {
+                            clipboard.set_text(ansi::strip(diff_view.text())).ok();
+                        }
                     }
                 }
-            },
+            }
         };
 
         view_tx.send(app.screen.clone())?;
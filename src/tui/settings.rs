@@ -0,0 +1,253 @@
+use std::{str::FromStr, sync::OnceLock};
+
+use diffsoup::error::{CustomError, Result};
+use error_stack::{Report, ResultExt};
+use jj_lib::settings::UserSettings;
+use ratatui::style::Color;
+use syntect::highlighting::Theme as SyntaxTheme;
+
+use crate::tui::highlight::{load_theme, theme_names};
+
+static THEME: OnceLock<Theme> = OnceLock::new();
+
+/// Character keys that are always bound to navigation and can't be configured.
+const FIXED_KEYS: [char; 11] = ['j', 'k', 'l', 'g', 'G', '[', ']', '{', '}', '<', '>'];
+
+/// The `[diffsoup]` settings that only affect the TUI.
+#[derive(Debug, Clone)]
+pub struct UiSettings {
+    pub show_unchanged: bool,
+    pub keys: KeyBindings,
+    pub theme: Theme,
+    /// One of syntect's default themes, resolved from `diffsoup.colors.syntax-theme`
+    pub syntax_theme: SyntaxTheme,
+}
+
+/// Keys for the actions that are bound to a single letter, navigation keys are fixed.
+#[derive(Debug, Clone)]
+pub struct KeyBindings {
+    pub quit: char,
+    pub diffstat: char,
+    pub copy: char,
+    pub expand: char,
+    pub toggle_unchanged: char,
+    pub toggle_trunk_merges: char,
+    pub toggle_filter: char,
+    pub toggle_upstream: char,
+    pub toggle_format: char,
+    pub toggle_split: char,
+    pub toggle_highlight: char,
+    pub external_tool: char,
+}
+
+#[derive(Debug, Clone)]
+pub struct Theme {
+    pub added: Color,
+    pub removed: Color,
+    pub hunk: Color,
+    pub file_header: Color,
+    /// Header, titles and borders
+    pub accent: Color,
+    /// Commits with a changed description
+    pub reworded: Color,
+    /// Commits with changed contents or metadata
+    pub changed: Color,
+    /// Unchanged commits, statistics and upstream changes
+    pub dimmed: Color,
+    pub merge: Color,
+    pub conflict: Color,
+    /// Changed metadata fields in the commit list
+    pub metadata: Color,
+    /// Background of added lines when syntax highlighting
+    pub added_background: Color,
+    /// Background of removed lines when syntax highlighting
+    pub removed_background: Color,
+}
+
+impl UiSettings {
+    pub fn from_settings(settings: &UserSettings) -> Result<Self> {
+        let key = |name: &str| -> Result<char> {
+            let value = settings
+                .get_string(["diffsoup", "keys", name])
+                .change_context(CustomError::ConfigError)?;
+            let mut chars = value.chars();
+            match (chars.next(), chars.next()) {
+                (Some(key), None) => Ok(key),
+                _ => Err(Report::new(CustomError::ConfigError)
+                    .attach(format!("diffsoup.keys.{name} must be a single character"))),
+            }
+        };
+        let color = |name: &str| -> Result<Color> {
+            let value = settings
+                .get_string(["diffsoup", "colors", name])
+                .change_context(CustomError::ConfigError)?;
+            Color::from_str(&value).map_err(|_| {
+                Report::new(CustomError::ConfigError)
+                    .attach(format!("diffsoup.colors.{name}: unknown color {value:?}"))
+            })
+        };
+
+        let syntax_theme_name = settings
+            .get_string("diffsoup.colors.syntax-theme")
+            .change_context(CustomError::ConfigError)?;
+        let syntax_theme = load_theme(&syntax_theme_name).ok_or_else(|| {
+            Report::new(CustomError::ConfigError).attach(format!(
+                "diffsoup.colors.syntax-theme: unknown theme {syntax_theme_name:?}, available: {}",
+                theme_names().join(", ")
+            ))
+        })?;
+
+        let keys = KeyBindings {
+            quit: key("quit")?,
+            diffstat: key("diffstat")?,
+            copy: key("copy")?,
+            expand: key("expand")?,
+            toggle_unchanged: key("toggle-unchanged")?,
+            toggle_trunk_merges: key("toggle-trunk-merges")?,
+            toggle_filter: key("toggle-filter")?,
+            toggle_upstream: key("toggle-upstream")?,
+            toggle_format: key("toggle-format")?,
+            toggle_split: key("toggle-split")?,
+            toggle_highlight: key("toggle-highlight")?,
+            external_tool: key("external-tool")?,
+        };
+        keys.check_conflicts()?;
+
+        Ok(Self {
+            show_unchanged: settings
+                .get_bool("diffsoup.show-unchanged")
+                .change_context(CustomError::ConfigError)?,
+            keys,
+            theme: Theme {
+                added: color("added")?,
+                removed: color("removed")?,
+                hunk: color("hunk")?,
+                file_header: color("file-header")?,
+                accent: color("accent")?,
+                reworded: color("reworded")?,
+                changed: color("changed")?,
+                dimmed: color("dimmed")?,
+                merge: color("merge")?,
+                conflict: color("conflict")?,
+                metadata: color("metadata")?,
+                added_background: color("added-background")?,
+                removed_background: color("removed-background")?,
+            },
+            syntax_theme,
+        })
+    }
+}
+
+impl KeyBindings {
+    /// Fails if two actions share a key or one is bound to a fixed navigation key.
+    fn check_conflicts(&self) -> Result<()> {
+        let bindings = [
+            ("quit", self.quit),
+            ("diffstat", self.diffstat),
+            ("copy", self.copy),
+            ("expand", self.expand),
+            ("toggle-unchanged", self.toggle_unchanged),
+            ("toggle-trunk-merges", self.toggle_trunk_merges),
+            ("toggle-filter", self.toggle_filter),
+            ("toggle-upstream", self.toggle_upstream),
+            ("toggle-format", self.toggle_format),
+            ("toggle-split", self.toggle_split),
+            ("toggle-highlight", self.toggle_highlight),
+            ("external-tool", self.external_tool),
+        ];
+        for (index, (name, key)) in bindings.iter().enumerate() {
+            if FIXED_KEYS.contains(key) {
+                return Err(Report::new(CustomError::ConfigError).attach(format!(
+                    "diffsoup.keys.{name}: {key:?} is reserved for navigation"
+                )));
+            }
+            if let Some((other, _)) = bindings[..index].iter().find(|(_, other)| other == key) {
+                return Err(Report::new(CustomError::ConfigError).attach(format!(
+                    "diffsoup.keys.{name}: {key:?} is already bound to {other}"
+                )));
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Self {
+            added: Color::Green,
+            removed: Color::Red,
+            hunk: Color::Cyan,
+            file_header: Color::Yellow,
+            accent: Color::Cyan,
+            reworded: Color::Cyan,
+            changed: Color::Yellow,
+            dimmed: Color::DarkGray,
+            merge: Color::Blue,
+            conflict: Color::Magenta,
+            metadata: Color::LightBlue,
+            added_background: Color::Rgb(0x16, 0x36, 0x1d),
+            removed_background: Color::Rgb(0x45, 0x18, 0x1b),
+        }
+    }
+}
+
+/// Sets the theme used by all drawing functions, only the first call has an effect.
+pub fn set_theme(theme: Theme) {
+    let _ = THEME.set(theme);
+}
+
+pub fn theme() -> &'static Theme {
+    THEME.get_or_init(Theme::default)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn keys() -> KeyBindings {
+        KeyBindings {
+            quit: 'q',
+            diffstat: 'd',
+            copy: 'y',
+            expand: 'e',
+            toggle_unchanged: 'h',
+            toggle_trunk_merges: 'm',
+            toggle_filter: 'f',
+            toggle_upstream: 't',
+            toggle_format: 'w',
+            toggle_split: 's',
+            toggle_highlight: 'c',
+            external_tool: 'x',
+        }
+    }
+
+    #[test]
+    fn default_keys_dont_conflict() {
+        assert!(keys().check_conflicts().is_ok());
+    }
+
+    #[test]
+    fn duplicate_keys_conflict() {
+        let keys = KeyBindings {
+            external_tool: 'q',
+            ..keys()
+        };
+        let error = keys.check_conflicts().unwrap_err();
+        assert!(
+            format!("{error:?}")
+                .contains("diffsoup.keys.external-tool: 'q' is already bound to quit")
+        );
+    }
+
+    #[test]
+    fn navigation_keys_are_reserved() {
+        for key in FIXED_KEYS {
+            let keys = KeyBindings {
+                copy: key,
+                ..keys()
+            };
+            let error = keys.check_conflicts().unwrap_err();
+            assert!(format!("{error:?}").contains("reserved for navigation"));
+        }
+    }
+}
@@ -1,15 +1,24 @@
-use std::sync::mpsc::Sender;
+use std::{
+    collections::HashSet,
+    sync::{Arc, mpsc::Sender},
+};
 
 use diffsoup::{
-    diff::CommitDiff,
+    diff::{CommitDiff, RenderFormat},
+    external::ExternalDiff,
+    patch::{self, SideBySideRow},
     pr::{PageDirection, Pagination},
 };
 use jj_lib::ref_name::RefNameBuf;
-use ratatui::widgets::ListState;
+use ratatui::{text::Line, widgets::ListState};
 
-use crate::tui::{
-    JobId,
-    worker::{WorkerMsg, WorkerRequest, WorkerResponse},
+use crate::{
+    ansi,
+    tui::{
+        JobId,
+        highlight::Highlights,
+        worker::{InterdiffResponse, WorkerMsg, WorkerRequest, WorkerResponse},
+    },
 };
 
 #[derive(Debug)]
@@ -18,6 +27,16 @@ pub struct AppState {
     pub screen_size: (u16, u16),
     pub list_state: ListState,
     pub show_unchanged: bool,
+    /// Whether the path filter is applied, `None` if no filter was given
+    pub filter_enabled: Option<bool>,
+    pub diff_format: RenderFormat,
+    pub split_view: bool,
+    pub syntax_highlight: bool,
+    /// Whether an external diff tool is configured
+    pub has_external_tool: bool,
+    pub hide_trunk_merges: bool,
+    /// Diff view to return to after the external tool exits
+    pub suspended_view: Option<DiffView>,
     pub commit_list: Vec<RefNameBuf>,
     pub next_page: Option<Pagination>,
     pub base_index: usize,
@@ -33,6 +52,9 @@ pub enum AppScreen {
     Error(Option<String>),
     List(ListView),
     DiffView(DiffView),
+    DiffStat(DiffStatView),
+    /// The terminal is handed over to an external diff tool
+    External(Arc<ExternalDiff>),
 }
 
 #[derive(Debug, Clone)]
@@ -40,6 +62,8 @@ pub struct ListView {
     pub commits: Vec<CommitDiff>,
     pub list_state: ListState,
     pub show_unchanged: bool,
+    pub filter_enabled: Option<bool>,
+    pub hide_trunk_merges: bool,
     pub base_name: String,
     pub base_index: usize,
     pub comparison_name: String,
@@ -57,11 +81,134 @@ impl ListView {
     }
 }
 
+/// Per-file statistics of a single list entry.
+#[derive(Debug, Clone)]
+pub struct DiffStatView {
+    pub commit: CommitDiff,
+    pub list_state: ListState,
+}
+
 #[derive(Debug, Clone)]
 pub struct DiffView {
     pub commit: String,
+    pub from: Option<String>,
+    pub to: Option<String>,
+    pub format: RenderFormat,
+    /// Changes to author, committer, signature and the like, shown above the file diffs
+    pub metadata_diff: Option<String>,
+    /// Changes to the commit description, shown above the file diffs
+    pub message_diff: Option<String>,
     pub diff: String,
+    /// Files rendered with all conflict sides because the rebase didn't apply cleanly
+    pub conflicts: Vec<String>,
+    /// Binary and large files that are only summarized
+    pub collapsed: Vec<String>,
+    /// One line per collapsed file, shown above the file diffs
+    pub collapsed_summary: Option<String>,
+    /// Whether collapsing was turned off for this view
+    pub expanded: bool,
+    /// Changes between the old and new base, limited to the files in this interdiff
+    pub upstream_diff: Option<String>,
+    /// Lines of `diff` belonging to hunks that also appear in `upstream_diff`
+    pub upstream_lines: HashSet<usize>,
+    pub show_upstream: bool,
+    /// Show old and new lines side by side instead of a unified diff
+    pub split: bool,
+    /// Syntax highlight the code in hunks
+    pub highlight: bool,
+    /// Highlighted lines of `diff`, if it was rendered with highlighting enabled
+    pub highlights: Option<Arc<Highlights>>,
+    /// Highlighted lines of `upstream_diff`
+    pub upstream_highlights: Option<Arc<Highlights>>,
+    pub has_external_tool: bool,
     pub scroll: u16,
+    /// Shown in the footer until the next key press
+    pub notice: Option<String>,
+}
+
+impl DiffView {
+    pub fn text(&self) -> &str {
+        match &self.upstream_diff {
+            Some(upstream_diff) if self.show_upstream => upstream_diff,
+            _ => &self.diff,
+        }
+    }
+
+    /// Whether the side by side view is active, only git diffs can be split
+    pub fn is_split(&self) -> bool {
+        self.split && self.format == RenderFormat::Git
+    }
+
+    /// Returns the highlighted version of a line of the shown text, if highlighting is enabled.
+    pub fn highlighted(&self, line: &str) -> Option<Line<'static>> {
+        if !self.highlight {
+            return None;
+        }
+        let highlights = match &self.upstream_diff {
+            Some(_) if self.show_upstream => self.upstream_highlights.as_ref(),
+            _ => self.highlights.as_ref(),
+        };
+        highlights?.get(self.text(), line).cloned()
+    }
+
+    /// Titled sections shown above the file diffs, unless showing the upstream changes
+    pub fn header_sections(&self) -> Vec<(&'static str, &str)> {
+        if self.show_upstream {
+            return Vec::new();
+        }
+        [
+            ("Metadata", self.metadata_diff.as_deref()),
+            ("Commit message", self.message_diff.as_deref()),
+            ("Collapsed files", self.collapsed_summary.as_deref()),
+        ]
+        .into_iter()
+        .filter_map(|(title, section)| Some((title, section?)))
+        .collect()
+    }
+
+    pub fn line_count(&self) -> usize {
+        let diff_lines = if self.is_split() {
+            patch::side_by_side(self.text()).len()
+        } else {
+            self.text().lines().count()
+        };
+        self.header_line_count() + diff_lines
+    }
+
+    /// Returns the rendered line at which the section of the file `path` starts. Only git diffs
+    /// have file headers that can be found reliably.
+    pub fn file_offset(&self, path: &str) -> Option<usize> {
+        if self.format != RenderFormat::Git {
+            return None;
+        }
+        let text = ansi::strip(self.text());
+        let file = patch::parse(&text)
+            .into_iter()
+            .find(|file| file.path == path)?;
+        let offset = if self.is_split() {
+            let header = text.lines().nth(file.start)?;
+            patch::side_by_side(self.text())
+                .iter()
+                .position(|row| *row == SideBySideRow::Header(header))?
+        } else {
+            file.start
+        };
+        Some(self.header_line_count() + offset)
+    }
+
+    fn header_line_count(&self) -> usize {
+        // title and separator around each section
+        self.header_sections()
+            .iter()
+            .map(|(_, section)| {
+                if self.is_split() {
+                    patch::side_by_side_hunk(section).len() + 2
+                } else {
+                    section.lines().count() + 2
+                }
+            })
+            .sum()
+    }
 }
 
 #[derive(Debug)]
@@ -71,9 +218,22 @@ pub enum UiEvent {
     SizeChange((u16, u16)),
     PatchsetChange((usize, usize)),
     EnterDiff(usize),
+    EnterDiffStat(usize),
+    /// Open the diff view at the file with this index in the diffstat
+    JumpToFile(usize),
     BackToList,
     ToggleUnchanged,
+    ToggleFilter,
+    ToggleTrunkMerges,
+    ToggleUpstream,
+    ToggleFormat,
+    ToggleSplit,
+    ToggleHighlight,
+    ExpandCollapsed,
     CopyToClipboard,
+    RunExternalTool,
+    /// The external tool exited, with an error message if it couldn't be run
+    ExternalToolExited(Option<String>),
 }
 
 #[derive(Debug)]
@@ -86,7 +246,6 @@ pub enum ScrollDirection {
 pub enum ScrollEvent {
     Top,
     Single(ScrollDirection),
-    Multi((ScrollDirection, u16)),
     HalfPage(ScrollDirection),
     FullPage(ScrollDirection),
     Bottom,
@@ -96,7 +255,6 @@ impl ScrollEvent {
     pub fn get_new_index(&self, screen_size: (u16, u16), current: usize, length: usize) -> usize {
         let (amount, direction) = match self {
             ScrollEvent::Single(direction) => (1, direction),
-            ScrollEvent::Multi((direction, amount)) => (*amount as usize, direction),
             ScrollEvent::HalfPage(direction) => ((screen_size.1 as usize) / 2, direction),
             ScrollEvent::FullPage(direction) => (screen_size.1.into(), direction),
             ScrollEvent::Top => {
@@ -114,12 +272,26 @@ impl ScrollEvent {
 }
 
 impl AppState {
-    pub fn new(worker_req_tx: Sender<WorkerMsg<WorkerRequest>>) -> Self {
+    pub fn new(
+        worker_req_tx: Sender<WorkerMsg<WorkerRequest>>,
+        has_filter: bool,
+        diff_format: RenderFormat,
+        has_external_tool: bool,
+        hide_trunk_merges: bool,
+        show_unchanged: bool,
+    ) -> Self {
         Self {
             screen: AppScreen::Loading(None),
             screen_size: (0, 0),
             list_state: ListState::default(),
-            show_unchanged: false,
+            show_unchanged,
+            filter_enabled: has_filter.then_some(true),
+            diff_format,
+            split_view: false,
+            syntax_highlight: false,
+            has_external_tool,
+            hide_trunk_merges,
+            suspended_view: None,
             commit_list: Vec::new(),
             next_page: None,
             base_index: 0,
@@ -133,10 +305,31 @@ impl AppState {
         self.current_job.map(JobId::next).unwrap_or_default()
     }
 
+    /// Width to render interdiffs at, which is that of one pane in the side by side view.
+    pub fn render_width(&self, format: RenderFormat) -> u16 {
+        let width = self.screen_size.0;
+        if self.split_view && format == RenderFormat::Git {
+            // Outer border on both sides, separator on the left pane
+            (width.saturating_sub(2) / 2).saturating_sub(1)
+        } else {
+            width
+        }
+    }
+
+    pub fn apply_filter(&self) -> bool {
+        self.filter_enabled.unwrap_or_default()
+    }
+
     pub fn handle_worker(&mut self, response: WorkerResponse) {
         match response {
             WorkerResponse::Error(msg) => self.screen = AppScreen::Error(Some(msg)),
             WorkerResponse::Loading(msg) => self.screen = AppScreen::Loading(Some(msg)),
+            WorkerResponse::ExternalDiff(external_diff) => {
+                if let AppScreen::DiffView(diff_view) = &self.screen {
+                    self.suspended_view = Some(diff_view.clone());
+                    self.screen = AppScreen::External(external_diff);
+                }
+            }
             WorkerResponse::LoadCommits { page } => {
                 let length = page.items.len();
                 // insert new at start
@@ -157,6 +350,8 @@ impl AppState {
                                 from_index: from,
                                 to: self.commit_list[to].as_str().to_string(),
                                 to_index: to,
+                                apply_filter: self.apply_filter(),
+                                hide_trunk_merges: self.hide_trunk_merges,
                             },
                         });
                         self.current_job = Some(job_id);
@@ -184,6 +379,8 @@ impl AppState {
                 self.screen = AppScreen::List(ListView {
                     list_state: self.list_state.clone().with_selected(Some(selected)),
                     show_unchanged: self.show_unchanged,
+                    filter_enabled: self.filter_enabled,
+                    hide_trunk_merges: self.hide_trunk_merges,
                     base_name: self
                         .commit_list
                         .get(from)
@@ -218,16 +415,65 @@ impl AppState {
                     self.current_job = Some(job_id);
                 };
             }
-            WorkerResponse::RenderInterdiff {
-                title,
-                diff,
-                scroll,
-            } => {
-                self.screen = AppScreen::DiffView(DiffView {
+            WorkerResponse::RenderInterdiff(response) => {
+                let InterdiffResponse {
+                    title,
+                    from,
+                    to,
+                    format,
+                    metadata_diff,
+                    message_diff,
+                    diff,
+                    conflicts,
+                    collapsed,
+                    collapsed_summary,
+                    expanded,
+                    upstream_diff,
+                    upstream_lines,
+                    highlights,
+                    upstream_highlights,
+                    scroll,
+                    jump_to,
+                } = *response;
+                let mut diff_view = DiffView {
                     commit: title,
+                    from,
+                    to,
+                    format,
+                    metadata_diff,
+                    message_diff,
                     diff,
+                    conflicts,
+                    collapsed,
+                    collapsed_summary,
+                    expanded,
+                    upstream_diff,
+                    upstream_lines,
+                    show_upstream: false,
+                    split: self.split_view,
+                    highlight: self.syntax_highlight,
+                    highlights,
+                    upstream_highlights,
+                    has_external_tool: self.has_external_tool,
                     scroll,
-                });
+                    notice: None,
+                };
+                if let Some(path) = jump_to {
+                    match diff_view.file_offset(&path) {
+                        Some(offset) => diff_view.scroll = offset.try_into().unwrap_or(u16::MAX),
+                        None if diff_view.format != RenderFormat::Git => {
+                            diff_view.notice = Some(format!(
+                                "Jump to file not supported in {} diffs",
+                                diff_view.format
+                            ));
+                        }
+                        None if diff_view.collapsed.contains(&path) => {
+                            diff_view.notice = Some(format!("{path} is collapsed"));
+                        }
+                        None => diff_view.notice = Some(format!("No diff shown for {path}")),
+                    }
+                }
+                self.screen = AppScreen::DiffView(diff_view);
             }
         }
     }
@@ -1,11 +1,15 @@
 use std::{
+    collections::HashSet,
     sync::{Arc, mpsc::Receiver},
     thread::JoinHandle,
 };
 
 use diffsoup::{
-    diff::{CommitDiff, calculate_branch_diff, get_commit},
+    collapse::render_collapsed_files,
+    diff::{CommitDiff, DiffOptions, RenderFormat, calculate_branch_diff, get_commit},
     error::{CustomError, Result},
+    external::ExternalDiff,
+    patch,
     pr::{Page, Pagination, PrFetcher},
     repo::{ensure_commits_exist, fetch_commits},
     trees::DiffTree,
@@ -16,8 +20,12 @@ use jj_lib::{
     repo::{ReadonlyRepo, Repo},
     workspace::Workspace,
 };
+use syntect::highlighting::Theme as SyntaxTheme;
 
-use crate::tui::{JobId, WorkerSender};
+use crate::tui::{
+    JobId, WorkerSender,
+    highlight::{Highlights, highlight_diff},
+};
 
 #[derive(Debug, Clone)]
 pub struct WorkerMsg<T> {
@@ -35,12 +43,28 @@ pub enum WorkerRequest {
         from_index: usize,
         to: String,
         to_index: usize,
+        apply_filter: bool,
+        hide_trunk_merges: bool,
     },
     RenderInterdiff {
         from: Option<String>,
         to: Option<String>,
         render_width: u16,
         scroll: u16,
+        apply_filter: bool,
+        format: RenderFormat,
+        /// Diff binary and large files instead of summarizing them
+        expand: bool,
+        /// Syntax highlight git diffs
+        highlight: bool,
+        /// Scroll to the section of this file once rendered
+        jump_to: Option<String>,
+    },
+    /// Materialize the trees of a diff for running the external tool interactively
+    PrepareExternalDiff {
+        from: Option<String>,
+        to: Option<String>,
+        apply_filter: bool,
     },
 }
 
@@ -53,14 +77,33 @@ pub enum WorkerResponse {
         from: usize,
         to: usize,
     },
-    RenderInterdiff {
-        title: String,
-        diff: String,
-        scroll: u16,
-    },
+    RenderInterdiff(Box<InterdiffResponse>),
     LoadCommits {
         page: Page<RefNameBuf>,
     },
+    ExternalDiff(Arc<ExternalDiff>),
+}
+
+/// A rendered interdiff, boxed in the response since it is much larger than the other variants.
+#[derive(Debug, Clone)]
+pub struct InterdiffResponse {
+    pub title: String,
+    pub from: Option<String>,
+    pub to: Option<String>,
+    pub format: RenderFormat,
+    pub metadata_diff: Option<String>,
+    pub message_diff: Option<String>,
+    pub diff: String,
+    pub conflicts: Vec<String>,
+    pub collapsed: Vec<String>,
+    pub collapsed_summary: Option<String>,
+    pub expanded: bool,
+    pub upstream_diff: Option<String>,
+    pub upstream_lines: HashSet<usize>,
+    pub highlights: Option<Arc<Highlights>>,
+    pub upstream_highlights: Option<Arc<Highlights>>,
+    pub scroll: u16,
+    pub jump_to: Option<String>,
 }
 
 pub fn spawn_worker_thread(
@@ -69,14 +112,18 @@ pub fn spawn_worker_thread(
     workspace: Workspace,
     repo: Arc<ReadonlyRepo>,
     pr_fetcher: Box<dyn PrFetcher>,
+    options: DiffOptions,
+    syntax_theme: SyntaxTheme,
 ) -> JoinHandle<Result<()>> {
     let mut repo = repo;
+    let unfiltered_options = options.without_filter();
     std::thread::spawn(move || {
         while let Ok(request) = worker_request_rx.recv() {
             let response = match request.msg {
                 WorkerRequest::LoadCommits { pagination } => {
                     match pr_fetcher.fetch_history(pagination.as_ref()) {
                         Ok(page) => {
+                            let page = page.map(|iteration| iteration.name);
                             let missing = ensure_commits_exist(page.items.iter(), repo.as_ref())?;
                             if !missing.is_empty() {
                                 worker_response_tx
@@ -102,19 +149,72 @@ pub fn spawn_worker_thread(
                     from_index,
                     to,
                     to_index,
-                } => calculate_branch_diff(&from, &to, &workspace, repo.as_ref())
-                    .map(|diff| WorkerResponse::CalculateBranchDiff {
-                        commits: diff,
-                        from: from_index,
-                        to: to_index,
-                    })
-                    .unwrap_or_else(|e| WorkerResponse::Error(format!("{:#?}", e))),
+                    apply_filter,
+                    hide_trunk_merges,
+                } => calculate_branch_diff(
+                    &from,
+                    &to,
+                    &workspace,
+                    repo.as_ref(),
+                    &DiffOptions {
+                        hide_trunk_merges,
+                        ..if apply_filter {
+                            options.clone()
+                        } else {
+                            unfiltered_options.clone()
+                        }
+                    },
+                )
+                .map(|diff| WorkerResponse::CalculateBranchDiff {
+                    commits: diff,
+                    from: from_index,
+                    to: to_index,
+                })
+                .unwrap_or_else(|e| WorkerResponse::Error(format!("{:#?}", e))),
                 WorkerRequest::RenderInterdiff {
                     from,
                     to,
                     render_width,
                     scroll,
-                } => render_interdiff(&from, &to, &workspace, repo.as_ref(), render_width, scroll),
+                    apply_filter,
+                    format,
+                    expand,
+                    highlight,
+                    jump_to,
+                } => render_interdiff(
+                    from,
+                    to,
+                    &workspace,
+                    repo.as_ref(),
+                    render_width,
+                    scroll,
+                    highlight.then_some(&syntax_theme),
+                    jump_to,
+                    &DiffOptions {
+                        format,
+                        collapse: if expand { None } else { options.collapse },
+                        ..if apply_filter {
+                            options.clone()
+                        } else {
+                            unfiltered_options.clone()
+                        }
+                    },
+                ),
+                WorkerRequest::PrepareExternalDiff {
+                    from,
+                    to,
+                    apply_filter,
+                } => prepare_external_diff(
+                    from,
+                    to,
+                    &workspace,
+                    repo.as_ref(),
+                    if apply_filter {
+                        &options
+                    } else {
+                        &unfiltered_options
+                    },
+                ),
             };
             worker_response_tx
                 .send(WorkerMsg {
@@ -129,13 +229,17 @@ pub fn spawn_worker_thread(
     })
 }
 
+#[allow(clippy::too_many_arguments)]
 pub fn render_interdiff(
-    from_sha: &Option<String>,
-    to_sha: &Option<String>,
+    from_sha: Option<String>,
+    to_sha: Option<String>,
     workspace: &Workspace,
     repo: &impl Repo,
     render_width: u16,
     scroll: u16,
+    syntax_theme: Option<&SyntaxTheme>,
+    jump_to: Option<String>,
+    options: &DiffOptions,
 ) -> WorkerResponse {
     let from_commit = from_sha
         .as_ref()
@@ -152,13 +256,86 @@ pub fn render_interdiff(
 
     trees
         .map(|tree| {
-            diffsoup::diff::render_interdiff(&tree, workspace, repo, render_width)
-                .map(|diff| WorkerResponse::RenderInterdiff {
+            let interdiff =
+                diffsoup::diff::render_interdiff(&tree, workspace, repo, render_width, options)?;
+            let upstream_diff = diffsoup::diff::render_upstream_diff(
+                &tree,
+                workspace,
+                repo,
+                render_width,
+                options,
+            )?;
+            let upstream_lines = upstream_diff
+                .as_ref()
+                .map(|upstream| patch::upstream_lines(&interdiff.diff, upstream))
+                .unwrap_or_default();
+            // Only git diffs are highlighted
+            let highlight_text = |text: &str| {
+                syntax_theme
+                    .filter(|_| options.format == RenderFormat::Git)
+                    .map(|syntax_theme| Arc::new(highlight_diff(text, syntax_theme)))
+            };
+            let highlights = highlight_text(&interdiff.diff);
+            let upstream_highlights = upstream_diff.as_deref().and_then(highlight_text);
+            Ok(WorkerResponse::RenderInterdiff(Box::new(
+                InterdiffResponse {
                     title: format!("{tree}"),
-                    diff,
+                    from: from_sha.clone(),
+                    to: to_sha.clone(),
+                    format: options.format,
+                    metadata_diff: interdiff.metadata_diff,
+                    message_diff: interdiff.message_diff,
+                    diff: interdiff.diff,
+                    conflicts: interdiff.conflicts,
+                    collapsed: interdiff
+                        .collapsed
+                        .iter()
+                        .map(|file| file.path.as_internal_file_string().to_owned())
+                        .collect(),
+                    collapsed_summary: (!interdiff.collapsed.is_empty())
+                        .then(|| render_collapsed_files(&interdiff.collapsed)),
+                    expanded: options.collapse.is_none(),
+                    upstream_diff,
+                    upstream_lines,
+                    highlights,
+                    upstream_highlights,
                     scroll,
-                })
-                .unwrap_or_else(|e| WorkerResponse::Error(format!("{:#?}", e)))
+                    jump_to: jump_to.clone(),
+                },
+            )))
+        })
+        .map(|response: Result<WorkerResponse>| {
+            response.unwrap_or_else(|e| WorkerResponse::Error(format!("{:#?}", e)))
+        })
+        .unwrap_or(WorkerResponse::Error(
+            "no commits in this diff to render".to_string(),
+        ))
+}
+
+pub fn prepare_external_diff(
+    from_sha: Option<String>,
+    to_sha: Option<String>,
+    workspace: &Workspace,
+    repo: &impl Repo,
+    options: &DiffOptions,
+) -> WorkerResponse {
+    let Some(tool) = options.external_tool.clone() else {
+        return WorkerResponse::Error("no external diff tool configured".to_string());
+    };
+    let from_commit = from_sha
+        .map(|sha| get_commit(&sha, workspace, repo))
+        .transpose()
+        .unwrap_or(None);
+    let to_commit = to_sha
+        .map(|sha| get_commit(&sha, workspace, repo))
+        .transpose()
+        .unwrap_or(None);
+
+    DiffTree::from(from_commit.as_ref(), to_commit.as_ref())
+        .map(|tree| ExternalDiff::new(tool, &tree, repo, options))
+        .map(|external_diff| match external_diff {
+            Ok(external_diff) => WorkerResponse::ExternalDiff(Arc::new(external_diff)),
+            Err(e) => WorkerResponse::Error(format!("{:#?}", e)),
         })
         .unwrap_or(WorkerResponse::Error(
             "no commits in this diff to render".to_string(),